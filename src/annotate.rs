@@ -0,0 +1,1313 @@
+//! Vector annotations drawn over the selection and flattened into exports
+//!
+//! Text is laid out with Pango for whatever cairo context it is drawn on, so
+//! exports render it at the screenshot's own resolution rather than scaling
+//! up what was shown on screen.
+
+use crate::redact::{RedactStyle, Redaction};
+use crate::selection::Rect;
+use gtk4::{cairo, pango};
+use std::borrow::Cow;
+
+/// Colors offered when the config doesn't define any
+pub const DEFAULT_COLORS: &[[u8; 3]] = &[
+    [0xe0, 0x1b, 0x24],
+    [0xf6, 0xd3, 0x2d],
+    [0x33, 0xd1, 0x7a],
+    [0x35, 0x84, 0xe4],
+    [0x00, 0x00, 0x00],
+    [0xff, 0xff, 0xff],
+];
+
+/// Default stroke width in pixels
+pub const DEFAULT_WIDTH: f32 = 4.0;
+
+/// Stroke width limits and step
+pub const MIN_WIDTH: f32 = 1.0;
+pub const MAX_WIDTH: f32 = 32.0;
+const WIDTH_STEP: f32 = 2.0;
+
+/// Shortest drag that creates an annotation
+const MIN_LENGTH: f32 = 3.0;
+
+/// Distance the pointer moves before a freehand stroke gets another point
+const STROKE_STEP: f32 = 2.0;
+
+/// Fraction of the stroke width drawn at the lightest pen pressure
+const MIN_PRESSURE: f32 = 0.2;
+
+/// Highlighter strokes are this many times wider than pen strokes
+const HIGHLIGHT_SCALE: f32 = 4.0;
+
+/// Opacity of highlighter strokes
+const HIGHLIGHT_ALPHA: f64 = 0.5;
+
+/// Default radius of step markers in pixels
+pub const DEFAULT_STEP_RADIUS: f32 = 14.0;
+
+/// Step marker radius limits and step
+pub const MIN_STEP_RADIUS: f32 = 8.0;
+pub const MAX_STEP_RADIUS: f32 = 64.0;
+const STEP_RADIUS_STEP: f32 = 2.0;
+
+/// Opacity of the black drawn outside a spotlight, the same as outside the
+/// selection
+pub const DIM_ALPHA: f64 = 0.5;
+
+/// Magnification of magnify insets
+pub const MAGNIFY_ZOOM: f32 = 3.0;
+
+/// Gap between the magnified area and its inset
+const MAGNIFY_MARGIN: f32 = 20.0;
+
+/// Font family of text boxes when the config doesn't set one
+pub const DEFAULT_FONT: &str = "Sans";
+
+/// Default text size in pixels
+pub const DEFAULT_FONT_SIZE: f32 = 24.0;
+
+/// Text size limits and step
+pub const MIN_FONT_SIZE: f32 = 8.0;
+pub const MAX_FONT_SIZE: f32 = 200.0;
+const FONT_SIZE_STEP: f32 = 2.0;
+
+/// Kind of annotation drawn by dragging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationTool {
+    Arrow,
+    Rectangle,
+    Ellipse,
+    Line,
+    /// Placed by clicking rather than dragging, then typed into
+    Text,
+    /// Obscures the pixels under the dragged box
+    Redact(RedactStyle),
+    Pen,
+    Highlighter,
+    /// Numbered circles placed by clicking
+    Step,
+    /// Dims everything but the dragged box
+    Spotlight,
+    /// A zoomed copy of the dragged box next to it
+    Magnify,
+}
+
+impl AnnotationTool {
+    pub const ALL: [AnnotationTool; 13] = [
+        AnnotationTool::Arrow,
+        AnnotationTool::Rectangle,
+        AnnotationTool::Ellipse,
+        AnnotationTool::Line,
+        AnnotationTool::Pen,
+        AnnotationTool::Highlighter,
+        AnnotationTool::Text,
+        AnnotationTool::Step,
+        AnnotationTool::Spotlight,
+        AnnotationTool::Magnify,
+        AnnotationTool::Redact(RedactStyle::Blur),
+        AnnotationTool::Redact(RedactStyle::Pixelate),
+        AnnotationTool::Redact(RedactStyle::Fill),
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AnnotationTool::Arrow => "Arrow",
+            AnnotationTool::Rectangle => "Rectangle",
+            AnnotationTool::Ellipse => "Ellipse",
+            AnnotationTool::Line => "Line",
+            AnnotationTool::Text => "Text",
+            AnnotationTool::Redact(style) => style.label(),
+            AnnotationTool::Pen => "Pen",
+            AnnotationTool::Highlighter => "Highlighter",
+            AnnotationTool::Step => "Steps",
+            AnnotationTool::Spotlight => "Spotlight",
+            AnnotationTool::Magnify => "Magnify",
+        }
+    }
+}
+
+/// Geometry of an annotation in screen coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationShape {
+    /// A line with an arrowhead at `end`
+    Arrow {
+        start: (f32, f32),
+        end: (f32, f32),
+    },
+    Line {
+        start: (f32, f32),
+        end: (f32, f32),
+    },
+    Rectangle(Rect),
+    /// An ellipse inscribed in the rect
+    Ellipse(Rect),
+    Text(TextBox),
+    /// A redacted box, applied to the screenshot pixels rather than drawn
+    Redact(Rect, RedactStyle),
+    /// A smoothed freehand stroke
+    Pen(Vec<StrokePoint>),
+    /// A wide translucent stroke multiplied with the pixels beneath, so text
+    /// under it stays readable
+    Highlight(Vec<StrokePoint>),
+    /// A numbered circle; numbers follow the order markers were placed in
+    Step {
+        center: (f32, f32),
+        radius: f32,
+        number: u32,
+        style: StepStyle,
+    },
+    /// The lit box of a spotlight; only the latest spotlight is drawn
+    Spotlight(Rect),
+    /// The area shown magnified in an inset beside it
+    Magnify(Rect),
+}
+
+/// Look of step markers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StepStyle {
+    /// A disc of the annotation color with a contrasting number
+    #[default]
+    Filled,
+    /// A white disc ringed and numbered in the annotation color
+    Outline,
+}
+
+impl StepStyle {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "filled" => Some(StepStyle::Filled),
+            "outline" => Some(StepStyle::Outline),
+            _ => None,
+        }
+    }
+}
+
+/// A point of a freehand stroke
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokePoint {
+    pub x: f32,
+    pub y: f32,
+    /// Pen pressure from 0 to 1, 1 for devices without pressure
+    pub pressure: f32,
+}
+
+/// Text placed with its top-left corner at `position`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBox {
+    pub position: (f32, f32),
+    /// Typed text, lines separated by '\n'
+    pub text: String,
+    /// Font family name
+    pub font: String,
+    /// Font size in pixels
+    pub size: f32,
+    /// Whether to draw a rounded box behind the text
+    pub background: bool,
+}
+
+impl TextBox {
+    /// Space between the text and the edge of its background box
+    fn padding(&self) -> f32 {
+        (self.size * 0.25).round()
+    }
+
+    /// Lay the text out for a Pango context
+    pub fn layout(&self, context: &pango::Context) -> pango::Layout {
+        let layout = pango::Layout::new(context);
+        self.configure(&layout);
+        layout
+    }
+
+    fn configure(&self, layout: &pango::Layout) {
+        let mut font = pango::FontDescription::from_string(&self.font);
+        font.set_family(&self.font);
+        font.set_absolute_size(self.size as f64 * pango::SCALE as f64);
+        layout.set_font_description(Some(&font));
+        layout.set_text(&self.text);
+    }
+
+    /// Area covered by the text and its background box
+    pub fn bounds(&self, context: &pango::Context) -> Rect {
+        let (width, height) = self.layout(context).pixel_size();
+        let padding = self.padding();
+        Rect::new(
+            self.position.0 - padding,
+            self.position.1 - padding,
+            width as f32 + padding * 2.0,
+            height as f32 + padding * 2.0,
+        )
+    }
+
+    /// Insertion point after the last character, as a zero-width rect
+    pub fn caret(&self, context: &pango::Context) -> Rect {
+        let (strong, _) = self.layout(context).cursor_pos(self.text.len() as i32);
+        let scale = pango::SCALE as f32;
+        Rect::new(
+            self.position.0 + strong.x() as f32 / scale,
+            self.position.1 + strong.y() as f32 / scale,
+            0.0,
+            strong.height() as f32 / scale,
+        )
+    }
+
+    fn draw(&self, cr: &cairo::Context, color: [u8; 3]) {
+        if self.text.is_empty() {
+            return;
+        }
+        let layout = pangocairo::functions::create_layout(cr);
+        self.configure(&layout);
+
+        let [r, g, b] = color.map(|c| c as f64 / 255.0);
+        if self.background {
+            // Dark box behind light text, light box behind dark text
+            let (width, height) = layout.pixel_size();
+            let padding = self.padding() as f64;
+            if is_light(color) {
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.65);
+            } else {
+                cr.set_source_rgba(1.0, 1.0, 1.0, 0.85);
+            }
+            rounded_rect(
+                cr,
+                self.position.0 as f64 - padding,
+                self.position.1 as f64 - padding,
+                width as f64 + padding * 2.0,
+                height as f64 + padding * 2.0,
+                self.size as f64 * 0.3,
+            );
+            cr.fill().ok();
+        }
+
+        cr.set_source_rgb(r, g, b);
+        cr.move_to(self.position.0 as f64, self.position.1 as f64);
+        pangocairo::functions::show_layout(cr, &layout);
+    }
+}
+
+/// Whether black text reads better on the color than white
+fn is_light([r, g, b]: [u8; 3]) -> bool {
+    r as u32 * 299 + g as u32 * 587 + b as u32 * 114 > 127_500
+}
+
+/// Add a rounded rectangle to the current path
+pub fn rounded_rect(cr: &cairo::Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
+    use std::f64::consts::{FRAC_PI_2, PI};
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    cr.new_sub_path();
+    cr.arc(x + width - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    cr.arc(
+        x + width - radius,
+        y + height - radius,
+        radius,
+        0.0,
+        FRAC_PI_2,
+    );
+    cr.arc(x + radius, y + height - radius, radius, FRAC_PI_2, PI);
+    cr.arc(x + radius, y + radius, radius, PI, PI + FRAC_PI_2);
+    cr.close_path();
+}
+
+/// A shape with its stroke
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub shape: AnnotationShape,
+    pub color: [u8; 3],
+    pub width: f32,
+}
+
+impl Annotation {
+    /// Start an annotation of the given kind at a point
+    pub fn new(tool: AnnotationTool, start: (f32, f32), color: [u8; 3], width: f32) -> Self {
+        let shape = match tool {
+            AnnotationTool::Arrow => AnnotationShape::Arrow { start, end: start },
+            AnnotationTool::Line => AnnotationShape::Line { start, end: start },
+            AnnotationTool::Rectangle => {
+                AnnotationShape::Rectangle(Rect::new(start.0, start.1, 0.0, 0.0))
+            }
+            AnnotationTool::Ellipse => {
+                AnnotationShape::Ellipse(Rect::new(start.0, start.1, 0.0, 0.0))
+            }
+            AnnotationTool::Redact(style) => {
+                AnnotationShape::Redact(Rect::new(start.0, start.1, 0.0, 0.0), style)
+            }
+            AnnotationTool::Spotlight => {
+                AnnotationShape::Spotlight(Rect::new(start.0, start.1, 0.0, 0.0))
+            }
+            AnnotationTool::Magnify => {
+                AnnotationShape::Magnify(Rect::new(start.0, start.1, 0.0, 0.0))
+            }
+            AnnotationTool::Step => AnnotationShape::Step {
+                center: start,
+                radius: DEFAULT_STEP_RADIUS,
+                number: 1,
+                style: StepStyle::default(),
+            },
+            AnnotationTool::Pen | AnnotationTool::Highlighter => {
+                let point = StrokePoint {
+                    x: start.0,
+                    y: start.1,
+                    pressure: 1.0,
+                };
+                if tool == AnnotationTool::Pen {
+                    AnnotationShape::Pen(vec![point])
+                } else {
+                    AnnotationShape::Highlight(vec![point])
+                }
+            }
+            AnnotationTool::Text => AnnotationShape::Text(TextBox {
+                position: start,
+                text: String::new(),
+                font: DEFAULT_FONT.to_string(),
+                size: DEFAULT_FONT_SIZE,
+                background: false,
+            }),
+        };
+        Self {
+            shape,
+            color,
+            width,
+        }
+    }
+
+    /// Stretch the annotation from `start` to `end`, or continue a freehand
+    /// stroke to `end` with the given pen pressure. With `constrain`, lines
+    /// snap to multiples of 45°, boxes become squares or circles and strokes
+    /// become straight lines.
+    pub fn drag_to(&mut self, start: (f32, f32), end: (f32, f32), constrain: bool, pressure: f32) {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let (x, y) = end;
+        match &mut self.shape {
+            AnnotationShape::Arrow { end: e, .. } | AnnotationShape::Line { end: e, .. } => {
+                *e = if constrain {
+                    snap_angle(start, end)
+                } else {
+                    end
+                };
+            }
+            AnnotationShape::Rectangle(rect)
+            | AnnotationShape::Ellipse(rect)
+            | AnnotationShape::Redact(rect, _)
+            | AnnotationShape::Spotlight(rect)
+            | AnnotationShape::Magnify(rect) => {
+                let (w, h) = if constrain {
+                    let side = dx.abs().max(dy.abs());
+                    (side.copysign(dx), side.copysign(dy))
+                } else {
+                    (dx, dy)
+                };
+                *rect = Rect::new(start.0, start.1, w, h).normalized();
+            }
+            AnnotationShape::Pen(points) | AnnotationShape::Highlight(points) => {
+                // Straight from the first point while constrained
+                if constrain {
+                    let (x, y) = snap_angle(start, end);
+                    points.truncate(1);
+                    points.push(StrokePoint { x, y, pressure });
+                    return;
+                }
+                let last = points.last().copied();
+                if last.is_some_and(|p| (x - p.x).hypot(y - p.y) < STROKE_STEP) {
+                    return;
+                }
+                points.push(StrokePoint { x, y, pressure });
+            }
+            AnnotationShape::Step { center, .. } => *center = end,
+            AnnotationShape::Text(_) => {}
+        }
+    }
+
+    /// Whether the annotation is too small to keep
+    pub fn is_degenerate(&self) -> bool {
+        match &self.shape {
+            AnnotationShape::Arrow { start, end } | AnnotationShape::Line { start, end } => {
+                (end.0 - start.0).hypot(end.1 - start.1) < MIN_LENGTH
+            }
+            AnnotationShape::Rectangle(rect)
+            | AnnotationShape::Ellipse(rect)
+            | AnnotationShape::Redact(rect, _)
+            | AnnotationShape::Spotlight(rect)
+            | AnnotationShape::Magnify(rect) => rect.width < MIN_LENGTH && rect.height < MIN_LENGTH,
+            AnnotationShape::Text(text) => text.text.trim().is_empty(),
+            AnnotationShape::Step { .. } => false,
+            AnnotationShape::Pen(points) | AnnotationShape::Highlight(points) => {
+                let extent = |value: fn(&StrokePoint) -> f32| {
+                    let values = points.iter().map(value);
+                    values.clone().fold(f32::MIN, f32::max) - values.fold(f32::MAX, f32::min)
+                };
+                extent(|p| p.x) < MIN_LENGTH && extent(|p| p.y) < MIN_LENGTH
+            }
+        }
+    }
+
+    /// Draw the annotation in screen coordinates. Redactions draw nothing,
+    /// they are applied to the pixels beneath, see `redaction`.
+    pub fn draw(&self, cr: &cairo::Context) {
+        if let AnnotationShape::Text(text) = &self.shape {
+            text.draw(cr, self.color);
+            return;
+        }
+
+        let [r, g, b] = self.color;
+        cr.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+        cr.set_line_width(self.width as f64);
+        cr.set_line_cap(cairo::LineCap::Round);
+        cr.set_line_join(cairo::LineJoin::Round);
+
+        match &self.shape {
+            AnnotationShape::Line { start, end } => {
+                cr.move_to(start.0 as f64, start.1 as f64);
+                cr.line_to(end.0 as f64, end.1 as f64);
+                cr.stroke().ok();
+            }
+            AnnotationShape::Arrow { start, end } => {
+                let (sx, sy) = (start.0 as f64, start.1 as f64);
+                let (ex, ey) = (end.0 as f64, end.1 as f64);
+                let angle = (ey - sy).atan2(ex - sx);
+                let length = (ex - sx).hypot(ey - sy);
+                let head = (self.width as f64 * 4.0).max(12.0).min(length);
+                let spread = std::f64::consts::PI / 7.0;
+
+                // Stop the shaft inside the head so its round cap stays hidden
+                let shaft = length - head * 0.6;
+                cr.move_to(sx, sy);
+                cr.line_to(sx + shaft * angle.cos(), sy + shaft * angle.sin());
+                cr.stroke().ok();
+
+                cr.move_to(ex, ey);
+                cr.line_to(
+                    ex - head * (angle - spread).cos(),
+                    ey - head * (angle - spread).sin(),
+                );
+                cr.line_to(
+                    ex - head * (angle + spread).cos(),
+                    ey - head * (angle + spread).sin(),
+                );
+                cr.close_path();
+                cr.fill_preserve().ok();
+                cr.set_line_width((self.width as f64 / 2.0).max(1.0));
+                cr.stroke().ok();
+            }
+            AnnotationShape::Rectangle(rect) => {
+                cr.rectangle(
+                    rect.x as f64,
+                    rect.y as f64,
+                    rect.width as f64,
+                    rect.height as f64,
+                );
+                cr.stroke().ok();
+            }
+            AnnotationShape::Ellipse(rect) => {
+                // Scale a unit circle, then stroke unscaled for an even width
+                cr.save().ok();
+                cr.translate(
+                    (rect.x + rect.width / 2.0) as f64,
+                    (rect.y + rect.height / 2.0) as f64,
+                );
+                cr.scale(
+                    (rect.width / 2.0).max(0.5) as f64,
+                    (rect.height / 2.0).max(0.5) as f64,
+                );
+                cr.arc(0.0, 0.0, 1.0, 0.0, std::f64::consts::TAU);
+                cr.restore().ok();
+                cr.stroke().ok();
+            }
+            AnnotationShape::Pen(points) => {
+                let segments = smooth_segments(points);
+                let width = |p: &StrokePoint| (self.width * p.pressure.max(MIN_PRESSURE)) as f64;
+                if points.windows(2).all(|w| w[0].pressure == w[1].pressure) {
+                    cr.set_line_width(width(&points[0]));
+                    add_segments(cr, &segments);
+                    cr.stroke().ok();
+                } else {
+                    // One stroke per segment to follow the pressure
+                    for segment in &segments {
+                        cr.set_line_width(width(&points[segment.3]));
+                        add_segments(cr, std::slice::from_ref(segment));
+                        cr.stroke().ok();
+                    }
+                }
+            }
+            AnnotationShape::Highlight(points) => {
+                cr.save().ok();
+                cr.set_operator(cairo::Operator::Multiply);
+                cr.set_source_rgba(
+                    r as f64 / 255.0,
+                    g as f64 / 255.0,
+                    b as f64 / 255.0,
+                    HIGHLIGHT_ALPHA,
+                );
+                cr.set_line_width((self.width * HIGHLIGHT_SCALE) as f64);
+                add_segments(cr, &smooth_segments(points));
+                cr.stroke().ok();
+                cr.restore().ok();
+            }
+            AnnotationShape::Step {
+                center,
+                radius,
+                number,
+                style,
+            } => {
+                let (x, y, radius) = (center.0 as f64, center.1 as f64, *radius as f64);
+                cr.arc(x, y, radius, 0.0, std::f64::consts::TAU);
+                let light = match style {
+                    StepStyle::Filled => {
+                        cr.fill().ok();
+                        if is_light(self.color) {
+                            [0.0; 3]
+                        } else {
+                            [1.0; 3]
+                        }
+                    }
+                    StepStyle::Outline => {
+                        cr.set_source_rgb(1.0, 1.0, 1.0);
+                        cr.fill_preserve().ok();
+                        cr.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+                        cr.set_line_width((radius / 6.0).max(2.0));
+                        cr.stroke().ok();
+                        self.color.map(|c| c as f64 / 255.0)
+                    }
+                };
+
+                // Center the number, shrinking it as it gets more digits
+                let layout = pangocairo::functions::create_layout(cr);
+                let mut font = pango::FontDescription::from_string("Sans Bold");
+                let digits = number.to_string();
+                let size = radius * if digits.len() > 1 { 0.95 } else { 1.2 };
+                font.set_absolute_size(size * pango::SCALE as f64);
+                layout.set_font_description(Some(&font));
+                layout.set_text(&digits);
+                let (extents, _) = layout.pixel_extents();
+                cr.set_source_rgb(light[0], light[1], light[2]);
+                cr.move_to(
+                    x - extents.x() as f64 - extents.width() as f64 / 2.0,
+                    y - extents.y() as f64 - extents.height() as f64 / 2.0,
+                );
+                pangocairo::functions::show_layout(cr, &layout);
+            }
+            AnnotationShape::Magnify(_) => {
+                // The zoomed pixels are drawn beforehand, see `draw_annotations`
+                if let Some((source, inset)) = self.magnify_rects(clip_bounds(cr)) {
+                    for rect in [source, inset] {
+                        cr.rectangle(
+                            rect.x as f64,
+                            rect.y as f64,
+                            rect.width as f64,
+                            rect.height as f64,
+                        );
+                    }
+                    cr.stroke().ok();
+                }
+            }
+            AnnotationShape::Text(_)
+            | AnnotationShape::Redact(..)
+            | AnnotationShape::Spotlight(_) => {}
+        }
+    }
+
+    /// The whole pixels a magnify annotation zooms and where its inset goes
+    /// within the bounds: beside the area, on the left when there's no room
+    /// on the right. None for other annotations or areas out of the bounds.
+    pub fn magnify_rects(&self, bounds: Rect) -> Option<(Rect, Rect)> {
+        let AnnotationShape::Magnify(rect) = self.shape else {
+            return None;
+        };
+        let x = rect.x.floor();
+        let y = rect.y.floor();
+        let source = Rect::new(
+            x,
+            y,
+            (rect.x + rect.width - x).ceil().max(1.0),
+            (rect.y + rect.height - y).ceil().max(1.0),
+        );
+        let (right, bottom) = (bounds.x + bounds.width, bounds.y + bounds.height);
+        if source.x >= right
+            || source.y >= bottom
+            || source.x + source.width <= bounds.x
+            || source.y + source.height <= bounds.y
+        {
+            return None;
+        }
+
+        let width = source.width * MAGNIFY_ZOOM;
+        let height = source.height * MAGNIFY_ZOOM;
+        let mut x = source.x + source.width + MAGNIFY_MARGIN;
+        if x + width > right {
+            x = source.x - MAGNIFY_MARGIN - width;
+        }
+        let x = x.min(right - width).max(bounds.x);
+        let y = source.y.min(bottom - height).max(bounds.y);
+        Some((source, Rect::new(x, y, width, height)))
+    }
+
+    pub fn is_step(&self) -> bool {
+        matches!(self.shape, AnnotationShape::Step { .. })
+    }
+
+    /// Whether this is a step marker covering the point
+    pub fn is_step_at(&self, x: f32, y: f32) -> bool {
+        match self.shape {
+            AnnotationShape::Step { center, radius, .. } => {
+                (x - center.0).hypot(y - center.1) <= radius
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this is a highlighter stroke, drawn before other annotations
+    /// so it only tints the pixels
+    pub fn is_highlight(&self) -> bool {
+        matches!(self.shape, AnnotationShape::Highlight(_))
+    }
+
+    /// The pixels to obscure, if this is a redaction
+    pub fn redaction(&self) -> Option<Redaction> {
+        let AnnotationShape::Redact(rect, style) = self.shape else {
+            return None;
+        };
+        let x = rect.x.floor();
+        let y = rect.y.floor();
+        Some(Redaction {
+            region: (
+                x as i32,
+                y as i32,
+                (rect.x + rect.width - x).ceil() as i32,
+                (rect.y + rect.height - y).ceil() as i32,
+            ),
+            style,
+            color: self.color,
+        })
+    }
+}
+
+/// Snap the direction from `start` to `end` to a multiple of 45°
+fn snap_angle(start: (f32, f32), end: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let angle = dy.atan2(dx);
+    let snapped = (angle / std::f32::consts::FRAC_PI_4).round() * std::f32::consts::FRAC_PI_4;
+    let length = dx.hypot(dy);
+    (
+        start.0 + length * snapped.cos(),
+        start.1 + length * snapped.sin(),
+    )
+}
+
+/// Quadratic curve from a point, bent towards a control point, to another
+/// point, with the index of the stroke point it was made from
+type Segment = ((f64, f64), (f64, f64), (f64, f64), usize);
+
+/// Smooth a freehand stroke: curves run between the midpoints of
+/// consecutive points, using the points themselves as control points
+fn smooth_segments(points: &[StrokePoint]) -> Vec<Segment> {
+    let point = |i: usize| (points[i].x as f64, points[i].y as f64);
+    let midpoint = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let Some(last) = points.len().checked_sub(1) else {
+        return Vec::new();
+    };
+
+    let mut segments = Vec::with_capacity(points.len());
+    let mut from = point(0);
+    for i in 1..last {
+        let to = midpoint(point(i), point(i + 1));
+        segments.push((from, point(i), to, i));
+        from = to;
+    }
+    segments.push((from, midpoint(from, point(last)), point(last), last));
+    segments
+}
+
+/// Add segments to the current path, starting a new one at the first
+fn add_segments(cr: &cairo::Context, segments: &[Segment]) {
+    let Some(&(start, _, _, _)) = segments.first() else {
+        return;
+    };
+    cr.move_to(start.0, start.1);
+    for &(from, control, to, _) in segments {
+        // The cubic curve equivalent to the quadratic one
+        cr.curve_to(
+            from.0 + (control.0 - from.0) * 2.0 / 3.0,
+            from.1 + (control.1 - from.1) * 2.0 / 3.0,
+            to.0 + (control.0 - to.0) * 2.0 / 3.0,
+            to.1 + (control.1 - to.1) * 2.0 / 3.0,
+            to.0,
+            to.1,
+        );
+    }
+}
+
+/// Draw annotations in screen coordinates: highlighter strokes first, then
+/// the spotlight's dimming and the magnified insets, then the others in order
+pub fn draw_annotations(cr: &cairo::Context, annotations: &[Annotation]) {
+    // Insets zoom the pixels as they are before anything is drawn over them
+    let bounds = clip_bounds(cr);
+    let insets: Vec<_> = annotations
+        .iter()
+        .filter_map(|a| a.magnify_rects(bounds))
+        .filter_map(|(source, inset)| Some((inset, zoomed_pattern(cr, source, inset)?)))
+        .collect();
+
+    let (highlights, others): (Vec<_>, Vec<_>) = annotations.iter().partition(|a| a.is_highlight());
+    for annotation in highlights {
+        annotation.draw(cr);
+    }
+    if let Some(rect) = spotlight(annotations) {
+        cr.save().ok();
+        for rect in [bounds, rect] {
+            cr.rectangle(
+                rect.x as f64,
+                rect.y as f64,
+                rect.width as f64,
+                rect.height as f64,
+            );
+        }
+        // Atop leaves pixels outside a shaped selection transparent
+        cr.set_fill_rule(cairo::FillRule::EvenOdd);
+        cr.set_operator(cairo::Operator::Atop);
+        cr.set_source_rgba(0.0, 0.0, 0.0, DIM_ALPHA);
+        cr.fill().ok();
+        cr.restore().ok();
+    }
+    for (inset, pattern) in insets {
+        cr.rectangle(
+            inset.x as f64,
+            inset.y as f64,
+            inset.width as f64,
+            inset.height as f64,
+        );
+        cr.set_source(&*pattern).ok();
+        cr.fill().ok();
+    }
+    for annotation in others {
+        annotation.draw(cr);
+    }
+}
+
+/// The lit box of the latest spotlight
+pub fn spotlight<'a>(annotations: impl IntoIterator<Item = &'a Annotation>) -> Option<Rect> {
+    annotations
+        .into_iter()
+        .filter_map(|a| match a.shape {
+            AnnotationShape::Spotlight(rect) => Some(rect),
+            _ => None,
+        })
+        .last()
+}
+
+/// The area cairo draws to, in user coordinates
+fn clip_bounds(cr: &cairo::Context) -> Rect {
+    let (x1, y1, x2, y2) = cr.clip_extents().unwrap_or_default();
+    Rect::new(x1 as f32, y1 as f32, (x2 - x1) as f32, (y2 - y1) as f32)
+}
+
+/// A copy of the source pixels drawn so far, zoomed without smoothing to
+/// fill the inset
+fn zoomed_pattern(cr: &cairo::Context, source: Rect, inset: Rect) -> Option<cairo::SurfacePattern> {
+    let surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        source.width as i32,
+        source.height as i32,
+    )
+    .ok()?;
+    let copy = cairo::Context::new(&surface).ok()?;
+    let (x, y) = cr.user_to_device(source.x as f64, source.y as f64);
+    copy.set_source_surface(cr.target(), -x, -y).ok()?;
+    copy.paint().ok()?;
+    drop(copy);
+
+    let pattern = cairo::SurfacePattern::create(&surface);
+    pattern.set_filter(cairo::Filter::Nearest);
+    let mut matrix = cairo::Matrix::identity();
+    let scale = 1.0 / MAGNIFY_ZOOM as f64;
+    matrix.scale(scale, scale);
+    matrix.translate(-inset.x as f64, -inset.y as f64);
+    pattern.set_matrix(matrix);
+    Some(pattern)
+}
+
+/// A change to the annotations that undo and redo step through
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// An annotation was placed on top of the others
+    Add(Annotation),
+    /// The annotation at an index was deleted
+    Remove(usize, Annotation),
+}
+
+/// The annotation layer: finished annotations, the one being drawn and the
+/// tool and stroke used for new ones
+#[derive(Debug, Clone)]
+pub struct Annotations {
+    pub items: Vec<Annotation>,
+    /// Edits to undo and undone edits to redo, most recent last
+    pub undo_stack: Vec<Edit>,
+    pub redo_stack: Vec<Edit>,
+    /// Tool used by drags inside the selection, None to edit the selection
+    pub tool: Option<AnnotationTool>,
+    /// Colors cycled through for new annotations
+    pub palette: Vec<[u8; 3]>,
+    /// Index of the current color in the palette
+    pub color: usize,
+    /// Stroke width for new annotations
+    pub width: f32,
+    /// Annotation being drawn and the point its drag started at
+    pub drawing: Option<(Annotation, (f32, f32))>,
+    /// Font family, size and background of new text boxes
+    pub font: String,
+    pub font_size: f32,
+    pub text_background: bool,
+    /// Index of the text box being typed into
+    pub editing: Option<usize>,
+    /// Whether that text box was just placed. It only enters the history,
+    /// discarding the redo stack, once it is kept with some text.
+    editing_new: bool,
+    /// Text being composed by an input method, not yet committed
+    pub preedit: String,
+    /// Radius and look of new step markers
+    pub step_radius: f32,
+    pub step_style: StepStyle,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            tool: None,
+            palette: DEFAULT_COLORS.to_vec(),
+            color: 0,
+            width: DEFAULT_WIDTH,
+            drawing: None,
+            font: DEFAULT_FONT.to_string(),
+            font_size: DEFAULT_FONT_SIZE,
+            text_background: false,
+            editing: None,
+            editing_new: false,
+            preedit: String::new(),
+            step_radius: DEFAULT_STEP_RADIUS,
+            step_style: StepStyle::default(),
+        }
+    }
+}
+
+impl Annotations {
+    /// Current stroke color
+    pub fn color(&self) -> [u8; 3] {
+        self.palette
+            .get(self.color)
+            .copied()
+            .unwrap_or([0xff, 0, 0])
+    }
+
+    /// Switch to the next color in the palette
+    pub fn cycle_color(&mut self) {
+        if !self.palette.is_empty() {
+            self.color = (self.color + 1) % self.palette.len();
+        }
+    }
+
+    /// Make the stroke of new annotations thicker or thinner
+    pub fn adjust_width(&mut self, increase: bool) {
+        let step = if increase { WIDTH_STEP } else { -WIDTH_STEP };
+        self.width = (self.width + step).clamp(MIN_WIDTH, MAX_WIDTH);
+    }
+
+    /// Make new step markers larger or smaller
+    pub fn adjust_step_radius(&mut self, increase: bool) {
+        let step = if increase {
+            STEP_RADIUS_STEP
+        } else {
+            -STEP_RADIUS_STEP
+        };
+        self.step_radius = (self.step_radius + step).clamp(MIN_STEP_RADIUS, MAX_STEP_RADIUS);
+    }
+
+    /// Make new text, and the text box being edited, larger or smaller
+    pub fn adjust_font_size(&mut self, increase: bool) {
+        let step = if increase {
+            FONT_SIZE_STEP
+        } else {
+            -FONT_SIZE_STEP
+        };
+        self.set_font_size(self.font_size + step);
+    }
+
+    /// Set the size of new text and of the text box being edited
+    pub fn set_font_size(&mut self, size: f32) {
+        let size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.font_size = size;
+        if let Some(text) = self.editing_text_mut() {
+            text.size = size;
+        }
+    }
+
+    /// Set the font family of new text and of the text box being edited
+    pub fn set_font(&mut self, font: &str) {
+        self.font = font.to_string();
+        if let Some(text) = self.editing_text_mut() {
+            text.font = font.to_string();
+        }
+    }
+
+    /// Set whether new text, and the text box being edited, get a background
+    pub fn set_text_background(&mut self, background: bool) {
+        self.text_background = background;
+        if let Some(text) = self.editing_text_mut() {
+            text.background = background;
+        }
+    }
+
+    /// Start drawing with the current tool, or place a new text box with the
+    /// text tool. Returns false if no tool is active.
+    pub fn start(&mut self, x: f32, y: f32, pressure: Option<f32>) -> bool {
+        let Some(tool) = self.tool else {
+            return false;
+        };
+        let mut annotation = Annotation::new(tool, (x, y), self.color(), self.width);
+        match &mut annotation.shape {
+            AnnotationShape::Pen(points) => points[0].pressure = pressure.unwrap_or(1.0),
+            AnnotationShape::Step {
+                radius,
+                number,
+                style,
+                ..
+            } => {
+                *radius = self.step_radius;
+                *style = self.step_style;
+                *number = self.items.iter().filter(|a| a.is_step()).count() as u32 + 1;
+            }
+            _ => {}
+        }
+        if let AnnotationShape::Text(text) = &mut annotation.shape {
+            text.font = self.font.clone();
+            text.size = self.font_size;
+            text.background = self.text_background;
+            self.items.push(annotation);
+            self.editing = Some(self.items.len() - 1);
+            self.editing_new = true;
+            return true;
+        }
+        self.drawing = Some((annotation, (x, y)));
+        true
+    }
+
+    /// Type into an existing text box. Returns false if it isn't text.
+    pub fn edit_text(&mut self, index: usize) -> bool {
+        match self.items.get(index) {
+            Some(Annotation {
+                shape: AnnotationShape::Text(_),
+                ..
+            }) => {
+                self.editing = Some(index);
+                self.editing_new = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The text box being typed into
+    pub fn editing_text(&self) -> Option<&TextBox> {
+        match self.items.get(self.editing?) {
+            Some(Annotation {
+                shape: AnnotationShape::Text(text),
+                ..
+            }) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn editing_text_mut(&mut self) -> Option<&mut TextBox> {
+        match self.items.get_mut(self.editing?) {
+            Some(Annotation {
+                shape: AnnotationShape::Text(text),
+                ..
+            }) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Append committed text to the text box being edited
+    pub fn insert_text(&mut self, s: &str) {
+        if let Some(text) = self.editing_text_mut() {
+            text.text.push_str(s);
+        }
+    }
+
+    /// Remove the last character of the text box being edited
+    pub fn delete_char(&mut self) {
+        if let Some(text) = self.editing_text_mut() {
+            text.text.pop();
+        }
+    }
+
+    /// Stop typing, dropping the text box if it was left empty. Returns
+    /// whether the text box was kept, or None if none was being edited.
+    pub fn end_text(&mut self) -> Option<bool> {
+        let index = self.editing.take()?;
+        let new = std::mem::take(&mut self.editing_new);
+        self.preedit.clear();
+        if self.items.get(index).is_some_and(|a| a.is_degenerate()) {
+            self.items.remove(index);
+            return Some(false);
+        }
+        if new {
+            self.record(Edit::Add(self.items[index].clone()));
+        }
+        Some(true)
+    }
+
+    /// Update the annotation being drawn. Pen strokes use the pressure
+    /// when the device reports it.
+    pub fn update(&mut self, x: f32, y: f32, constrain: bool, pressure: Option<f32>) {
+        if let Some((annotation, start)) = &mut self.drawing {
+            annotation.drag_to(*start, (x, y), constrain, pressure.unwrap_or(1.0));
+        }
+    }
+
+    /// Finish the annotation being drawn, dropping it if it is too small.
+    /// Returns false if no annotation was being drawn.
+    pub fn finish(&mut self) -> bool {
+        let Some((annotation, _)) = self.drawing.take() else {
+            return false;
+        };
+        if !annotation.is_degenerate() {
+            self.push(annotation);
+        }
+        true
+    }
+
+    /// Add a finished annotation, discarding the redo stack
+    pub fn push(&mut self, annotation: Annotation) {
+        self.items.push(annotation.clone());
+        self.record(Edit::Add(annotation));
+    }
+
+    /// Remember an edit for undo, discarding the redo stack
+    fn record(&mut self, edit: Edit) {
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+    }
+
+    /// Revert the last edit. Returns false if there is none.
+    pub fn undo(&mut self) -> bool {
+        // An empty text box is only dropped, leaving the annotation before it
+        if self.end_text() == Some(false) {
+            return true;
+        }
+        let Some(edit) = self.undo_stack.pop() else {
+            return false;
+        };
+        let edit = match edit {
+            // Text boxes may have been edited since they were added
+            Edit::Add(annotation) => Edit::Add(self.items.pop().unwrap_or(annotation)),
+            Edit::Remove(index, annotation) => {
+                self.items.insert(index, annotation.clone());
+                Edit::Remove(index, annotation)
+            }
+        };
+        self.redo_stack.push(edit);
+        self.renumber_steps();
+        true
+    }
+
+    /// Apply the last reverted edit again. Returns false if there is none.
+    pub fn redo(&mut self) -> bool {
+        self.end_text();
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Add(annotation) => self.items.push(annotation.clone()),
+            Edit::Remove(index, _) => {
+                self.items.remove(*index);
+            }
+        }
+        self.undo_stack.push(edit);
+        self.renumber_steps();
+        true
+    }
+
+    /// Delete the topmost step marker at a point and renumber the others.
+    /// Returns false if there is none.
+    pub fn remove_step_at(&mut self, x: f32, y: f32) -> bool {
+        let Some(index) = self.items.iter().rposition(|a| a.is_step_at(x, y)) else {
+            return false;
+        };
+        let annotation = self.items.remove(index);
+        self.record(Edit::Remove(index, annotation));
+        self.renumber_steps();
+        true
+    }
+
+    /// Number step markers 1, 2, 3… in the order they were placed
+    fn renumber_steps(&mut self) {
+        let mut next = 1;
+        for annotation in &mut self.items {
+            if let AnnotationShape::Step { number, .. } = &mut annotation.shape {
+                *number = next;
+                next += 1;
+            }
+        }
+    }
+
+    /// Finished annotations followed by the one being drawn. Text still
+    /// being composed by an input method is shown in its text box.
+    pub fn visible(&self) -> impl Iterator<Item = Cow<'_, Annotation>> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, annotation)| match &annotation.shape {
+                AnnotationShape::Text(text)
+                    if self.editing == Some(i) && !self.preedit.is_empty() =>
+                {
+                    let mut text = text.clone();
+                    text.text.push_str(&self.preedit);
+                    Cow::Owned(Annotation {
+                        shape: AnnotationShape::Text(text),
+                        ..annotation.clone()
+                    })
+                }
+                _ => Cow::Borrowed(annotation),
+            })
+            .chain(
+                self.drawing
+                    .as_ref()
+                    .map(|(annotation, _)| Cow::Borrowed(annotation)),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drag an annotation with a tool from one point to another
+    fn draw(annotations: &mut Annotations, tool: AnnotationTool, from: (f32, f32), to: (f32, f32)) {
+        annotations.tool = Some(tool);
+        annotations.start(from.0, from.1, None);
+        annotations.update(to.0, to.1, false, None);
+        annotations.finish();
+    }
+
+    #[test]
+    fn undo_empty_text_box() {
+        let mut annotations = Annotations::default();
+        draw(
+            &mut annotations,
+            AnnotationTool::Arrow,
+            (0.0, 0.0),
+            (100.0, 100.0),
+        );
+        draw(
+            &mut annotations,
+            AnnotationTool::Line,
+            (0.0, 0.0),
+            (100.0, 0.0),
+        );
+        annotations.undo();
+
+        // Placing a text box and undoing at once only drops the box
+        annotations.tool = Some(AnnotationTool::Text);
+        annotations.start(50.0, 50.0, None);
+        assert!(annotations.undo());
+        assert_eq!(annotations.items.len(), 1);
+        assert!(annotations.editing.is_none());
+
+        // The undone line can still be redone
+        assert!(annotations.redo());
+        assert_eq!(annotations.items.len(), 2);
+    }
+
+    #[test]
+    fn text_box_clears_redo_once_kept() {
+        let mut annotations = Annotations::default();
+        draw(
+            &mut annotations,
+            AnnotationTool::Arrow,
+            (0.0, 0.0),
+            (100.0, 100.0),
+        );
+        annotations.undo();
+
+        annotations.tool = Some(AnnotationTool::Text);
+        annotations.start(50.0, 50.0, None);
+        assert_eq!(annotations.end_text(), Some(false));
+        assert_eq!(annotations.redo_stack.len(), 1);
+
+        annotations.start(50.0, 50.0, None);
+        annotations.insert_text("Note");
+        assert_eq!(annotations.end_text(), Some(true));
+        assert!(annotations.redo_stack.is_empty());
+        assert_eq!(annotations.end_text(), None);
+    }
+
+    fn step_numbers(annotations: &Annotations) -> Vec<(f32, u32)> {
+        annotations
+            .items
+            .iter()
+            .filter_map(|a| match a.shape {
+                AnnotationShape::Step { center, number, .. } => Some((center.0, number)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undo_step_removal() {
+        let mut annotations = Annotations::default();
+        for x in [100.0, 200.0, 300.0] {
+            draw(
+                &mut annotations,
+                AnnotationTool::Step,
+                (x, 100.0),
+                (x, 100.0),
+            );
+        }
+        assert!(annotations.remove_step_at(200.0, 100.0));
+        assert_eq!(step_numbers(&annotations), [(100.0, 1), (300.0, 2)]);
+        assert!(!annotations.remove_step_at(200.0, 100.0));
+
+        // The marker comes back in its place and the others follow it
+        assert!(annotations.undo());
+        assert_eq!(
+            step_numbers(&annotations),
+            [(100.0, 1), (200.0, 2), (300.0, 3)]
+        );
+        assert!(annotations.redo());
+        assert_eq!(step_numbers(&annotations), [(100.0, 1), (300.0, 2)]);
+    }
+
+    #[test]
+    fn steps_stay_numbered_in_any_order() {
+        let mut annotations = Annotations::default();
+        for x in [100.0, 200.0, 300.0] {
+            draw(
+                &mut annotations,
+                AnnotationTool::Step,
+                (x, 100.0),
+                (x, 100.0),
+            );
+        }
+        annotations.remove_step_at(100.0, 100.0);
+        annotations.undo();
+        annotations.undo();
+        assert_eq!(step_numbers(&annotations), [(100.0, 1), (200.0, 2)]);
+
+        annotations.redo();
+        annotations.redo();
+        assert_eq!(step_numbers(&annotations), [(200.0, 1), (300.0, 2)]);
+
+        // A new marker follows the last one
+        draw(
+            &mut annotations,
+            AnnotationTool::Step,
+            (400.0, 100.0),
+            (400.0, 100.0),
+        );
+        assert_eq!(
+            step_numbers(&annotations),
+            [(200.0, 1), (300.0, 2), (400.0, 3)]
+        );
+        assert!(!annotations.redo());
+    }
+}
@@ -0,0 +1,214 @@
+//! Presentation-style decoration of exported images: padding around them,
+//! a solid or gradient background, a drop shadow and rounded corners
+
+use crate::annotate::rounded_rect;
+use crate::compose;
+use crate::screenshot::{self, ScreenshotError};
+use gdk_pixbuf::Pixbuf;
+use gtk4::cairo;
+use gtk4::prelude::*;
+
+/// Largest padding around the image in pixels
+pub const MAX_PADDING: f32 = 256.0;
+
+/// Largest shadow size in pixels
+pub const MAX_SHADOW: f32 = 64.0;
+
+/// Largest corner radius in pixels
+pub const MAX_RADIUS: f32 = 64.0;
+
+/// Opacity of the shadow under the image
+const SHADOW_ALPHA: f64 = 0.4;
+
+/// Rounded rectangles stacked to fade the shadow out
+const SHADOW_STEPS: u32 = 16;
+
+/// Fill behind a decorated image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Solid([u8; 3]),
+    /// From the top-left to the bottom-right corner
+    Gradient([u8; 3], [u8; 3]),
+}
+
+impl Background {
+    /// Parse a color, or two for a gradient
+    /// Example: "#ffffff" or "#1c71d8 #33d17a"
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut colors = s.split_whitespace().map(compose::parse_color);
+        let background = match (colors.next()?, colors.next()) {
+            (from, Some(to)) => Background::Gradient(from?, to?),
+            (color, None) => Background::Solid(color?),
+        };
+        colors.next().is_none().then_some(background)
+    }
+}
+
+/// How an exported image is decorated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub padding: f32,
+    pub background: Background,
+    /// Size of the drop shadow, 0 for none
+    pub shadow: f32,
+    /// Corner radius of the image, 0 for square corners
+    pub radius: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            padding: 48.0,
+            background: Background::Solid([0xff, 0xff, 0xff]),
+            shadow: 24.0,
+            radius: 10.0,
+        }
+    }
+}
+
+/// A named style, offered in the export menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub style: Style,
+}
+
+/// Presets offered when the config doesn't define any
+pub fn default_presets() -> Vec<Preset> {
+    let preset = |name: &str, background| Preset {
+        name: name.to_string(),
+        style: Style {
+            background,
+            ..Style::default()
+        },
+    };
+    vec![
+        preset("light", Background::Solid([0xf6, 0xf5, 0xf4])),
+        preset("dark", Background::Solid([0x24, 0x1f, 0x31])),
+        preset(
+            "ocean",
+            Background::Gradient([0x1c, 0x71, 0xd8], [0x33, 0xd1, 0x7a]),
+        ),
+        preset(
+            "sunset",
+            Background::Gradient([0xe6, 0x61, 0x00], [0xc0, 0x1c, 0x28]),
+        ),
+    ]
+}
+
+impl Style {
+    /// Size of an image once decorated
+    pub fn size(&self, (width, height): (f64, f64)) -> (f64, f64) {
+        let padding = self.padding as f64 * 2.0;
+        (width + padding, height + padding)
+    }
+
+    /// Draw the background and the shadow, then the image with rounded
+    /// corners. `paint` draws the image in its own coordinates.
+    pub fn draw(
+        &self,
+        cr: &cairo::Context,
+        (width, height): (f64, f64),
+        paint: impl FnOnce(&cairo::Context),
+    ) {
+        let (page_width, page_height) = self.size((width, height));
+        let padding = self.padding as f64;
+        let radius = self.radius as f64;
+        let rgb = |[r, g, b]: [u8; 3]| (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+
+        match self.background {
+            Background::Solid(color) => {
+                let (r, g, b) = rgb(color);
+                cr.set_source_rgb(r, g, b);
+            }
+            Background::Gradient(from, to) => {
+                let gradient = cairo::LinearGradient::new(0.0, 0.0, page_width, page_height);
+                let (r, g, b) = rgb(from);
+                gradient.add_color_stop_rgb(0.0, r, g, b);
+                let (r, g, b) = rgb(to);
+                gradient.add_color_stop_rgb(1.0, r, g, b);
+                cr.set_source(&gradient).ok();
+            }
+        }
+        cr.paint().ok();
+
+        // Growing rings of faint shadow, dropped a little below the image
+        let shadow = self.shadow as f64;
+        if shadow > 0.0 {
+            cr.set_source_rgba(0.0, 0.0, 0.0, SHADOW_ALPHA / SHADOW_STEPS as f64);
+            for step in 1..=SHADOW_STEPS {
+                let spread = shadow * step as f64 / SHADOW_STEPS as f64;
+                rounded_rect(
+                    cr,
+                    padding - spread,
+                    padding - spread + shadow / 4.0,
+                    width + spread * 2.0,
+                    height + spread * 2.0,
+                    radius + spread,
+                );
+                cr.fill().ok();
+            }
+        }
+
+        cr.save().ok();
+        cr.translate(padding, padding);
+        rounded_rect(cr, 0.0, 0.0, width, height, radius);
+        cr.clip();
+        paint(cr);
+        cr.restore().ok();
+    }
+}
+
+/// Decorate an image, e.g. a cropped area before it's encoded
+pub fn apply(image: &Pixbuf, style: &Style) -> Result<Pixbuf, ScreenshotError> {
+    let size = (image.width() as f64, image.height() as f64);
+    let (width, height) = style.size(size);
+    screenshot::draw_image((width.ceil() as i32, height.ceil() as i32), |cr| {
+        style.draw(cr, size, |cr| {
+            cr.set_source_pixbuf(image, 0.0, 0.0);
+            cr.paint().ok();
+        });
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_background() {
+        assert_eq!(
+            Background::parse("#ffffff"),
+            Some(Background::Solid([0xff, 0xff, 0xff]))
+        );
+        assert_eq!(
+            Background::parse("  #1c71d8   #33d17a "),
+            Some(Background::Gradient([0x1c, 0x71, 0xd8], [0x33, 0xd1, 0x7a]))
+        );
+        for invalid in [
+            "",
+            "white",
+            "#fff",
+            "#1c71d8 blue",
+            "blue #1c71d8",
+            "#1c71d8 #33d17a #ffffff",
+        ] {
+            assert_eq!(Background::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn size_adds_padding_on_every_side() {
+        let style = Style {
+            padding: 20.0,
+            ..Style::default()
+        };
+        assert_eq!(style.size((800.0, 600.0)), (840.0, 640.0));
+        let flat = Style {
+            padding: 0.0,
+            ..style
+        };
+        assert_eq!(flat.size((800.0, 600.0)), (800.0, 600.0));
+    }
+}
@@ -1,15 +1,39 @@
 //! Custom canvas widget for screenshot display and selection
 
-use crate::selection::{DragMode, Rect, ResizeEdge, Selection};
+use crate::annotate::{
+    self, Annotation, AnnotationShape, AnnotationTool, Annotations, StepStyle, MAGNIFY_ZOOM,
+};
+use crate::beautify::{self, Preset, Style};
+use crate::compose::MultiExport;
+use crate::detect;
+use crate::hints::{HintInput, Hints};
+use crate::mask::Mask;
+use crate::pixels::Pixels;
+use crate::redact::{RedactStyle, Redaction};
+use crate::selection::{
+    DragMode, DragModifiers, PredefinedRegion, Rect, ResizeEdge, Selection, SelectionShape,
+    SizePreset, HANDLE_SIZE,
+};
+use crate::shadow::{self, Shadow, ShadowCrop};
+use crate::snap::EdgeMap;
+use crate::vector::{Callouts, ExportFormat};
+use crate::{trim, wand};
 use gdk_pixbuf::Pixbuf;
+use gtk4::cairo;
 use gtk4::gdk;
+use gtk4::gio;
 use gtk4::graphene;
 use gtk4::gsk;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
-use gtk4::{glib, EventControllerMotion, GestureDrag};
+use gtk4::{
+    glib, EventControllerKey, EventControllerMotion, EventControllerScroll,
+    EventControllerScrollFlags, GestureClick, GestureDrag, IMMulticontext,
+};
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// Callback type for selection change notifications
 pub type SelectionChangeCallback = Box<dyn Fn(Option<(i32, i32, i32, i32)>)>;
@@ -33,6 +57,53 @@ mod imp {
         pub cursors: RefCell<HashMap<&'static str, gdk::Cursor>>,
         /// Current cursor name (to avoid unnecessary updates)
         pub current_cursor: RefCell<&'static str>,
+        /// Named regions from the config, offered in the quick-pick menu
+        pub named_regions: RefCell<Vec<(String, Rect)>>,
+        /// Quick-pick menu for named regions
+        pub region_menu: RefCell<Option<gtk4::Popover>>,
+        /// Named region under the pointer in the quick-pick menu
+        pub highlighted_named_region: Cell<Option<usize>>,
+        /// Letter hints shown over regions and outputs in hint mode
+        pub hints: RefCell<Option<Hints>>,
+        /// Pixel data of the screenshot, used by the magic wand
+        pub pixels: RefCell<Option<Pixels>>,
+        /// Whether clicks select by color similarity
+        pub wand_mode: Cell<bool>,
+        /// Maximum per-channel color difference for the magic wand
+        pub wand_tolerance: Cell<u8>,
+        /// Last magic wand click and whether it kept the mask, refilled
+        /// when the tolerance changes
+        pub wand_seed: Cell<Option<(i32, i32, bool)>>,
+        /// Overlay dimming the parts of a masked selection outside the mask
+        pub mask_texture: RefCell<Option<(Rc<Mask>, gdk::Texture)>>,
+        /// Maximum per-channel color difference for trimming uniform borders
+        pub trim_tolerance: Cell<u8>,
+        /// How windows picked from predefined regions are cropped, and the
+        /// same per app
+        pub window_shadow: Cell<ShadowCrop>,
+        pub app_window_shadows: RefCell<Vec<(String, ShadowCrop)>>,
+        /// Arrows, boxes, lines and text drawn over the selection
+        pub annotations: RefCell<Annotations>,
+        /// Previews of the visible redactions, rebuilt when they change
+        pub redaction_textures: RefCell<Vec<(Redaction, graphene::Rect, gdk::Texture)>>,
+        /// Input method used while typing into a text box
+        pub im_context: IMMulticontext,
+        /// Key handler for text boxes, routed through the input method while
+        /// one is being edited
+        pub text_keys: EventControllerKey,
+        /// How several selected areas are exported
+        pub multi_export: Cell<MultiExport>,
+        /// Background behind several areas exported as one image, transparent
+        /// if unset
+        pub multi_background: Cell<Option<[u8; 3]>>,
+        /// Format of saved images
+        pub export_format: Cell<ExportFormat>,
+        /// Border and caption added to saved images
+        pub callouts: RefCell<Callouts>,
+        /// Beautify presets, the chosen one and whether exports use it
+        pub beautify_presets: RefCell<Vec<Preset>>,
+        pub beautify_preset: Cell<usize>,
+        pub beautify_enabled: Cell<bool>,
     }
 
     impl Default for Canvas {
@@ -49,6 +120,29 @@ mod imp {
                 cursor_inside: Cell::new(false),
                 cursors: RefCell::new(HashMap::new()),
                 current_cursor: RefCell::new("default"),
+                named_regions: RefCell::new(Vec::new()),
+                region_menu: RefCell::new(None),
+                highlighted_named_region: Cell::new(None),
+                hints: RefCell::new(None),
+                pixels: RefCell::new(None),
+                wand_mode: Cell::new(false),
+                wand_tolerance: Cell::new(wand::DEFAULT_TOLERANCE),
+                wand_seed: Cell::new(None),
+                mask_texture: RefCell::new(None),
+                trim_tolerance: Cell::new(trim::DEFAULT_TOLERANCE),
+                window_shadow: Cell::new(ShadowCrop::default()),
+                app_window_shadows: RefCell::new(Vec::new()),
+                annotations: RefCell::new(Annotations::default()),
+                redaction_textures: RefCell::new(Vec::new()),
+                im_context: IMMulticontext::new(),
+                text_keys: EventControllerKey::new(),
+                multi_export: Cell::new(MultiExport::default()),
+                multi_background: Cell::new(None),
+                export_format: Cell::new(ExportFormat::default()),
+                callouts: RefCell::new(Callouts::default()),
+                beautify_presets: RefCell::new(beautify::default_presets()),
+                beautify_preset: Cell::new(0),
+                beautify_enabled: Cell::new(false),
             }
         }
     }
@@ -67,9 +161,22 @@ mod imp {
             obj.set_focusable(true);
             obj.set_can_focus(true);
         }
+
+        fn dispose(&self) {
+            if let Some(popover) = self.region_menu.take() {
+                popover.unparent();
+            }
+        }
     }
 
     impl WidgetImpl for Canvas {
+        fn size_allocate(&self, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(width, height, baseline);
+            if let Some(ref popover) = *self.region_menu.borrow() {
+                popover.present();
+            }
+        }
+
         fn snapshot(&self, snapshot: &gtk4::Snapshot) {
             let widget = self.obj();
             let width = widget.width() as f32;
@@ -82,7 +189,7 @@ mod imp {
             }
 
             // Dim color (semi-transparent black)
-            let dim_color = gdk::RGBA::new(0.0, 0.0, 0.0, 0.5);
+            let dim_color = gdk::RGBA::new(0.0, 0.0, 0.0, annotate::DIM_ALPHA as f32);
 
             // Draw dimming overlay with selection cutout
             let selection = self.selection.borrow();
@@ -90,41 +197,47 @@ mod imp {
             // Get predefined regions info for drawing
             let hovered_region = selection.hovered_region;
             let predefined_regions = selection.predefined_regions.clone();
+            let stack_position = (selection.hover_stack.len() > 1)
+                .then(|| (selection.hover_depth + 1, selection.hover_stack.len()));
 
             if let Some(sel_rect) = selection.rect {
                 let sel_rect = sel_rect.normalized();
 
-                // Draw the dimming in 4 parts around the selection
-                // Top strip
-                if sel_rect.y > 0.0 {
-                    let top_rect = graphene::Rect::new(0.0, 0.0, width, sel_rect.y);
-                    snapshot.append_color(&dim_color, &top_rect);
-                }
+                let screen = graphene::Rect::new(0.0, 0.0, width, height);
+                dim_around(snapshot, sel_rect, &screen, &dim_color);
 
-                // Bottom strip
-                let bottom_y = sel_rect.y + sel_rect.height;
-                if bottom_y < height {
-                    let bottom_rect = graphene::Rect::new(0.0, bottom_y, width, height - bottom_y);
-                    snapshot.append_color(&dim_color, &bottom_rect);
+                // Undo the dimming over areas added with Ctrl+drag
+                for extra in &selection.extra_rects {
+                    self.draw_extra_rect(snapshot, extra.normalized(), width, height);
                 }
 
-                // Left strip (between top and bottom)
-                if sel_rect.x > 0.0 {
-                    let left_rect =
-                        graphene::Rect::new(0.0, sel_rect.y, sel_rect.x, sel_rect.height);
-                    snapshot.append_color(&dim_color, &left_rect);
+                // Dim the parts of a masked selection outside the mask
+                if let Some(mask) = selection.active_mask() {
+                    let texture = self.mask_texture(mask);
+                    let mask_rect = graphene::Rect::new(
+                        mask.x as f32,
+                        mask.y as f32,
+                        mask.width as f32,
+                        mask.height as f32,
+                    );
+                    snapshot.append_texture(&texture, &mask_rect);
                 }
 
-                // Right strip (between top and bottom)
-                let right_x = sel_rect.x + sel_rect.width;
-                if right_x < width {
-                    let right_rect =
-                        graphene::Rect::new(right_x, sel_rect.y, width - right_x, sel_rect.height);
-                    snapshot.append_color(&dim_color, &right_rect);
+                // Dim the parts of a shaped selection outside the shape
+                if selection.shape != SelectionShape::Rect {
+                    self.draw_selection_shape(snapshot, &selection, sel_rect);
                 }
 
-                // Draw selection border
-                let border_color = gdk::RGBA::new(1.0, 1.0, 1.0, 1.0);
+                // Annotations, clipped to the selected areas
+                self.draw_annotations(snapshot, &selection);
+
+                // Draw selection border, faint around shapes
+                let border_alpha = if selection.shape == SelectionShape::Rect {
+                    1.0
+                } else {
+                    0.35
+                };
+                let border_color = gdk::RGBA::new(1.0, 1.0, 1.0, border_alpha);
                 let border_width = 2.0;
 
                 // Top border
@@ -168,8 +281,32 @@ mod imp {
                     ),
                 );
 
-                // Draw 4 corner handles only
-                if let Some(handles) = selection.get_corner_handles() {
+                // Draw guides for the lines the selection snapped to
+                let guide_color = gdk::RGBA::new(0.21, 0.52, 0.89, 1.0);
+                for guide in &selection.snap_guides {
+                    let length = guide.end - guide.start;
+                    let rect = if guide.vertical {
+                        graphene::Rect::new(guide.pos - 1.0, guide.start, 2.0, length)
+                    } else {
+                        graphene::Rect::new(guide.start, guide.pos - 1.0, length, 2.0)
+                    };
+                    snapshot.append_color(&guide_color, &rect);
+                }
+
+                // Show the exact size while a size preset is active
+                if let Some(index) = selection.active_preset {
+                    if let Some(preset) = selection.size_presets.get(index) {
+                        let (_, _, w, h) = selection.get_crop_region().unwrap_or_default();
+                        let text = format!("{} × {}  ({})", w, h, preset.label);
+                        self.draw_label(snapshot, &text, sel_rect.x, sel_rect.y - 8.0, height);
+                    }
+                }
+
+                // Draw 4 corner handles only, once a polygon is complete
+                let handles = selection
+                    .get_corner_handles()
+                    .filter(|_| !selection.polygon_open);
+                if let Some(handles) = handles {
                     let handle_fill = gdk::RGBA::new(1.0, 1.0, 1.0, 1.0);
                     let handle_border_color = gdk::RGBA::new(0.3, 0.3, 0.3, 1.0);
 
@@ -237,6 +374,12 @@ mod imp {
                                 true, // show crosshair
                             );
                         }
+                        DragMode::Drawing | DragMode::MovingVertex(_) => {
+                            // Lasso points and vertices are placed at the cursor
+                            self.draw_crosshair_and_magnifier(
+                                snapshot, width, height, cursor_x, cursor_y, true,
+                            );
+                        }
                         DragMode::Moving => {
                             // Don't show magnifier when moving
                         }
@@ -248,7 +391,12 @@ mod imp {
                 snapshot.append_color(&dim_color, &full_rect);
 
                 // Draw predefined regions as clickable areas
-                self.draw_predefined_regions(snapshot, &predefined_regions, hovered_region);
+                self.draw_predefined_regions(
+                    snapshot,
+                    &predefined_regions,
+                    hovered_region,
+                    stack_position,
+                );
 
                 // Draw crosshair and magnifier when no selection exists
                 if self.cursor_inside.get() {
@@ -259,6 +407,86 @@ mod imp {
                     );
                 }
             }
+
+            // Show named regions while the quick-pick menu is open
+            let menu_open = self
+                .region_menu
+                .borrow()
+                .as_ref()
+                .is_some_and(|p| p.is_visible());
+            if menu_open {
+                let named_regions = self.named_regions.borrow();
+                let regions: Vec<PredefinedRegion> = named_regions
+                    .iter()
+                    .map(|(name, rect)| PredefinedRegion::labelled(*rect, name.as_str()))
+                    .collect();
+                let highlighted = self.highlighted_named_region.get();
+                for (i, (name, rect)) in named_regions.iter().enumerate() {
+                    if highlighted != Some(i) {
+                        self.draw_label(snapshot, name, rect.x, rect.y + 28.0, height);
+                    }
+                }
+                self.draw_predefined_regions(snapshot, &regions, highlighted, None);
+            }
+
+            if let Some(ref hints) = *self.hints.borrow() {
+                self.draw_hints(snapshot, hints);
+            }
+
+            // Show the tolerance next to the cursor in magic wand mode
+            if self.wand_mode.get() && self.cursor_inside.get() {
+                let text = format!(
+                    "Magic wand: tolerance {}  ([ / ])",
+                    self.wand_tolerance.get()
+                );
+                let (x, y) = (self.cursor_x.get() + 16.0, self.cursor_y.get() - 8.0);
+                self.draw_label(snapshot, &text, x, y, height);
+            }
+
+            // Show the annotation tool and stroke next to the cursor
+            let annotations = self.annotations.borrow();
+            if let (Some(tool), true) = (annotations.tool, self.cursor_inside.get()) {
+                let text = if tool == AnnotationTool::Text {
+                    format!(
+                        "{}: {} px {}  (C for color, [ / ] for size)",
+                        tool.label(),
+                        annotations.font_size,
+                        annotations.font
+                    )
+                } else if tool == AnnotationTool::Step {
+                    format!(
+                        "{}: next is {}  (right-click deletes, [ / ] for size)",
+                        tool.label(),
+                        annotations.items.iter().filter(|a| a.is_step()).count() + 1
+                    )
+                } else if tool == AnnotationTool::Spotlight {
+                    "Spotlight: drag the box to keep lit".to_string()
+                } else if let AnnotationTool::Redact(style) = tool {
+                    let color = if style == RedactStyle::Fill {
+                        "C for color, "
+                    } else {
+                        ""
+                    };
+                    format!("Redact: {}  ({}K for the next style)", tool.label(), color)
+                } else {
+                    format!(
+                        "{}: {} px  (C for color, [ / ] for width)",
+                        tool.label(),
+                        annotations.width
+                    )
+                };
+                let (x, y) = (self.cursor_x.get() + 16.0, self.cursor_y.get() - 8.0);
+                self.draw_label(snapshot, &text, x, y, height);
+
+                // Swatch of the stroke color under the label
+                let [r, g, b] = annotations.color();
+                let color =
+                    gdk::RGBA::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+                let swatch = graphene::Rect::new(x, y + 4.0, 24.0, 6.0);
+                snapshot.push_rounded_clip(&gsk::RoundedRect::from_rect(swatch, 3.0));
+                snapshot.append_color(&color, &swatch);
+                snapshot.pop();
+            }
         }
 
         fn measure(&self, orientation: gtk4::Orientation, _for_size: i32) -> (i32, i32, i32, i32) {
@@ -401,9 +629,6 @@ mod imp {
 
             // Draw pixels from pixbuf using nearest-neighbor scaling
             if let Some(ref pixbuf) = *self.pixbuf.borrow() {
-                let pb_width = pixbuf.width();
-                let pb_height = pixbuf.height();
-
                 // Center pixel position in source image
                 let center_px = cursor_x.floor() as i32;
                 let center_py = cursor_y.floor() as i32;
@@ -412,38 +637,11 @@ mod imp {
                 let src_x = center_px - (pixels_x / 2);
                 let src_y = center_py - (pixels_y / 2);
 
-                // Clamp to valid pixbuf bounds
-                let valid_src_x = src_x.max(0);
-                let valid_src_y = src_y.max(0);
-                let valid_end_x = (src_x + pixels_x).min(pb_width);
-                let valid_end_y = (src_y + pixels_y).min(pb_height);
-                let valid_width = (valid_end_x - valid_src_x).max(0);
-                let valid_height = (valid_end_y - valid_src_y).max(0);
-
-                if valid_width > 0 && valid_height > 0 {
-                    // Extract sub-region and scale with nearest-neighbor
-                    let sub_pixbuf =
-                        pixbuf.new_subpixbuf(valid_src_x, valid_src_y, valid_width, valid_height);
-
-                    let scale = pixel_size as i32;
-                    if let Some(scaled) = sub_pixbuf.scale_simple(
-                        valid_width * scale,
-                        valid_height * scale,
-                        gdk_pixbuf::InterpType::Nearest,
-                    ) {
-                        // Calculate offset for partial regions (when cursor is near edges)
-                        let offset_x = (valid_src_x - src_x) as f32 * pixel_size;
-                        let offset_y = (valid_src_y - src_y) as f32 * pixel_size;
-
-                        let texture = gdk::Texture::for_pixbuf(&scaled);
-                        let texture_rect = graphene::Rect::new(
-                            mag_x + offset_x,
-                            mag_y + offset_y,
-                            (valid_width * scale) as f32,
-                            (valid_height * scale) as f32,
-                        );
-                        snapshot.append_texture(&texture, &texture_rect);
-                    }
+                let region = (src_x, src_y, pixels_x, pixels_y);
+                if let Some((rect, texture)) =
+                    zoomed_texture(pixbuf, region, pixel_size as i32, &[])
+                {
+                    snapshot.append_texture(&texture, &rect.offset_r(mag_x, mag_y));
                 }
 
                 // Draw pixel grid lines
@@ -533,12 +731,365 @@ mod imp {
             );
         }
 
-        /// Draw predefined regions as clickable/highlightable areas
+        /// Draw a small text label with a rounded background. The label sits
+        /// above `y` (its bottom edge), or below it when there is no room.
+        fn draw_label(&self, snapshot: &gtk4::Snapshot, text: &str, x: f32, y: f32, height: f32) {
+            let layout = self.obj().create_pango_layout(Some(text));
+            let (text_width, text_height) = layout.pixel_size();
+            let padding = 6.0;
+            let label_width = text_width as f32 + padding * 2.0;
+            let label_height = text_height as f32 + padding;
+
+            let mut label_y = y - label_height;
+            if label_y < 0.0 {
+                label_y = (y + 16.0).min(height - label_height);
+            }
+            let label_x = x.max(0.0);
+
+            let bg_rect = graphene::Rect::new(label_x, label_y, label_width, label_height);
+            let bg_color = gdk::RGBA::new(0.12, 0.12, 0.12, 0.9);
+            snapshot.push_rounded_clip(&gsk::RoundedRect::from_rect(bg_rect, 4.0));
+            snapshot.append_color(&bg_color, &bg_rect);
+            snapshot.pop();
+
+            snapshot.save();
+            snapshot.translate(&graphene::Point::new(
+                label_x + padding,
+                label_y + padding / 2.0,
+            ));
+            snapshot.append_layout(&layout, &gdk::RGBA::new(1.0, 1.0, 1.0, 1.0));
+            snapshot.restore();
+        }
+
+        /// Draw the annotations over each selected area
+        fn draw_annotations(&self, snapshot: &gtk4::Snapshot, selection: &Selection) {
+            let annotations = self.annotations.borrow();
+            let visible: Vec<Cow<Annotation>> = annotations.visible().collect();
+
+            // Keep the previews of redactions that haven't changed
+            let mut textures = self.redaction_textures.borrow_mut();
+            let previous = std::mem::take(&mut *textures);
+            for redaction in visible.iter().filter_map(|a| a.redaction()) {
+                let preview = previous
+                    .iter()
+                    .find(|(cached, _, _)| *cached == redaction)
+                    .map(|(_, bounds, texture)| (*bounds, texture.clone()))
+                    .or_else(|| self.redaction_texture(&redaction));
+                if let Some((bounds, texture)) = preview {
+                    textures.push((redaction, bounds, texture));
+                }
+            }
+
+            if visible.is_empty() {
+                return;
+            }
+            let (highlights, others): (Vec<_>, Vec<_>) =
+                visible.iter().partition(|a| a.is_highlight());
+            let spotlight = annotate::spotlight(visible.iter().map(|a| a.as_ref()));
+            let dim_color = gdk::RGBA::new(0.0, 0.0, 0.0, annotate::DIM_ALPHA as f32);
+            let redactions: Vec<Redaction> = textures.iter().map(|(r, _, _)| *r).collect();
+            let pixbuf = self.pixbuf.borrow();
+            let screen =
+                graphene::Rect::new(0.0, 0.0, self.screen_width.get(), self.screen_height.get());
+            for area in selection.selected_areas() {
+                let bounds = graphene::Rect::new(area.x, area.y, area.width, area.height);
+                snapshot.push_clip(&bounds);
+
+                // Highlights are multiplied with the pixels, which need
+                // redrawing as the bottom of the blend
+                if !highlights.is_empty() {
+                    snapshot.push_blend(gsk::BlendMode::Multiply);
+                    if let Some(ref texture) = *self.texture.borrow() {
+                        snapshot.append_texture(texture, &screen);
+                    }
+                }
+                // Redactions replace the pixels under every other annotation
+                for (_, rect, texture) in textures.iter() {
+                    snapshot.append_texture(texture, rect);
+                }
+                if !highlights.is_empty() {
+                    snapshot.pop();
+                    let cr = snapshot.append_cairo(&bounds);
+                    for annotation in &highlights {
+                        annotation.draw(&cr);
+                    }
+                    drop(cr);
+                    snapshot.pop();
+                }
+
+                // The latest spotlight dims the rest of the area, leaving
+                // the annotations drawn next undimmed
+                if let Some(rect) = spotlight {
+                    dim_around(snapshot, rect, &bounds, &dim_color);
+                }
+
+                // Insets zoom the redacted screenshot; their borders are
+                // drawn with the other annotations
+                for (source, inset) in visible.iter().filter_map(|a| a.magnify_rects(area)) {
+                    let region = (
+                        source.x as i32,
+                        source.y as i32,
+                        source.width as i32,
+                        source.height as i32,
+                    );
+                    let zoomed = pixbuf.as_ref().and_then(|pixbuf| {
+                        zoomed_texture(pixbuf, region, MAGNIFY_ZOOM as i32, &redactions)
+                    });
+                    if let Some((rect, texture)) = zoomed {
+                        snapshot.push_clip(&graphene::Rect::new(
+                            inset.x,
+                            inset.y,
+                            inset.width,
+                            inset.height,
+                        ));
+                        snapshot.append_texture(&texture, &rect.offset_r(inset.x, inset.y));
+                        snapshot.pop();
+                    }
+                }
+
+                let cr = snapshot.append_cairo(&bounds);
+                for annotation in &others {
+                    annotation.draw(&cr);
+                }
+                snapshot.pop();
+            }
+
+            // Caret at the end of the text box being typed into
+            if let Some(caret) = self.obj().text_caret() {
+                let [r, g, b] = annotations.color();
+                let color =
+                    gdk::RGBA::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+                let caret = graphene::Rect::new(caret.x - 1.0, caret.y, 2.0, caret.height);
+                snapshot.append_color(&color, &caret);
+            }
+        }
+
+        /// Draw an extra selected area: the undimmed screenshot with a border
+        fn draw_extra_rect(&self, snapshot: &gtk4::Snapshot, rect: Rect, width: f32, height: f32) {
+            let area = graphene::Rect::new(rect.x, rect.y, rect.width, rect.height);
+            if let Some(ref texture) = *self.texture.borrow() {
+                snapshot.push_clip(&area);
+                snapshot.append_texture(texture, &graphene::Rect::new(0.0, 0.0, width, height));
+                snapshot.pop();
+            }
+
+            let border = gsk::RoundedRect::from_rect(area.inset_r(-2.0, -2.0), 0.0);
+            let color = gdk::RGBA::new(1.0, 1.0, 1.0, 0.8);
+            snapshot.append_border(&border, &[2.0; 4], &[color; 4]);
+        }
+
+        /// Draw the outline of an ellipse, polygon or lasso selection and dim
+        /// the rest of its bounding box. Polygons get a handle per vertex and,
+        /// while being placed, a line to the cursor.
+        fn draw_selection_shape(
+            &self,
+            snapshot: &gtk4::Snapshot,
+            selection: &Selection,
+            sel_rect: Rect,
+        ) {
+            let cursor = (self.cursor_x.get() as f64, self.cursor_y.get() as f64);
+            let margin = HANDLE_SIZE;
+            let (mut x0, mut y0) = (sel_rect.x - margin, sel_rect.y - margin);
+            let (mut x1, mut y1) = (sel_rect.right() + margin, sel_rect.bottom() + margin);
+            if selection.polygon_open {
+                x0 = x0.min(cursor.0 as f32 - margin);
+                y0 = y0.min(cursor.1 as f32 - margin);
+                x1 = x1.max(cursor.0 as f32 + margin);
+                y1 = y1.max(cursor.1 as f32 + margin);
+            }
+            let cr = snapshot.append_cairo(&graphene::Rect::new(x0, y0, x1 - x0, y1 - y0));
+
+            let trace_shape = |cr: &cairo::Context| match selection.shape {
+                SelectionShape::Rect => {}
+                SelectionShape::Ellipse => {
+                    cr.save().ok();
+                    cr.translate(
+                        (sel_rect.x + sel_rect.width / 2.0) as f64,
+                        (sel_rect.y + sel_rect.height / 2.0) as f64,
+                    );
+                    cr.scale(
+                        (sel_rect.width / 2.0).max(0.5) as f64,
+                        (sel_rect.height / 2.0).max(0.5) as f64,
+                    );
+                    cr.arc(0.0, 0.0, 1.0, 0.0, std::f64::consts::TAU);
+                    cr.restore().ok();
+                }
+                SelectionShape::Polygon | SelectionShape::Lasso => {
+                    for (i, &(x, y)) in selection.outline.iter().enumerate() {
+                        if i == 0 {
+                            cr.move_to(x as f64, y as f64);
+                        } else {
+                            cr.line_to(x as f64, y as f64);
+                        }
+                    }
+                    if !selection.polygon_open {
+                        cr.close_path();
+                    }
+                }
+            };
+
+            // Dim the bounding box outside the shape
+            if !selection.polygon_open {
+                cr.set_fill_rule(cairo::FillRule::EvenOdd);
+                cr.rectangle(
+                    sel_rect.x as f64,
+                    sel_rect.y as f64,
+                    sel_rect.width as f64,
+                    sel_rect.height as f64,
+                );
+                trace_shape(&cr);
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.5);
+                cr.fill().ok();
+            }
+
+            // Outline
+            trace_shape(&cr);
+            if selection.polygon_open {
+                cr.line_to(cursor.0, cursor.1);
+            }
+            cr.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+            cr.set_line_width(2.0);
+            cr.stroke().ok();
+
+            // Vertex handles
+            if selection.shape == SelectionShape::Polygon {
+                let size = HANDLE_SIZE as f64 * 0.7;
+                for &(x, y) in &selection.outline {
+                    cr.rectangle(x as f64 - size / 2.0, y as f64 - size / 2.0, size, size);
+                }
+                cr.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+                cr.fill_preserve().ok();
+                cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+                cr.set_line_width(1.0);
+                cr.stroke().ok();
+            }
+        }
+
+        /// Get the overlay texture for a mask, building it on first use
+        fn mask_texture(&self, mask: &Rc<Mask>) -> gdk::Texture {
+            if let Some((ref cached, ref texture)) = *self.mask_texture.borrow() {
+                if Rc::ptr_eq(cached, mask) {
+                    return texture.clone();
+                }
+            }
+
+            let mut data = Vec::with_capacity((mask.width * mask.height * 4) as usize);
+            for y in mask.y..mask.y + mask.height {
+                for x in mask.x..mask.x + mask.width {
+                    let alpha = if mask.contains(x, y) { 0 } else { 128 };
+                    data.extend_from_slice(&[0, 0, 0, alpha]);
+                }
+            }
+            let texture = gdk::MemoryTexture::new(
+                mask.width,
+                mask.height,
+                gdk::MemoryFormat::R8g8b8a8,
+                &glib::Bytes::from_owned(data),
+                (mask.width * 4) as usize,
+            )
+            .upcast::<gdk::Texture>();
+
+            *self.mask_texture.borrow_mut() = Some((mask.clone(), texture.clone()));
+            texture
+        }
+
+        /// Preview of a redaction: its part of the screenshot, obscured the
+        /// same way the export will be
+        fn redaction_texture(
+            &self,
+            redaction: &Redaction,
+        ) -> Option<(graphene::Rect, gdk::Texture)> {
+            let pixels = self.pixels.borrow();
+            let pixels = pixels.as_ref()?;
+            let (x, y, w, h) = redaction.region;
+            let (left, top) = (x.max(0), y.max(0));
+            let (right, bottom) = ((x + w).min(pixels.width), (y + h).min(pixels.height));
+            if left >= right || top >= bottom {
+                return None;
+            }
+
+            let (width, height) = (right - left, bottom - top);
+            let mut data = pixels.rgba(left, top, width, height);
+            let local = Redaction {
+                region: (0, 0, width, height),
+                ..*redaction
+            };
+            local.apply(&mut data, (width, height), (width * 4) as usize);
+            let texture = gdk::MemoryTexture::new(
+                width,
+                height,
+                gdk::MemoryFormat::R8g8b8a8,
+                &glib::Bytes::from_owned(data),
+                (width * 4) as usize,
+            )
+            .upcast::<gdk::Texture>();
+            let bounds = graphene::Rect::new(left as f32, top as f32, width as f32, height as f32);
+            Some((bounds, texture))
+        }
+
+        /// Draw the outline of every region that still matches the typed
+        /// letters, with its hint in the top-left corner
+        fn draw_hints(&self, snapshot: &gtk4::Snapshot, hints: &Hints) {
+            let regions: Vec<PredefinedRegion> = hints
+                .visible_targets()
+                .map(|t| PredefinedRegion {
+                    rect: t.rect,
+                    label: None,
+                    app: None,
+                })
+                .collect();
+            self.draw_predefined_regions(snapshot, &regions, None, None);
+
+            let bg_color = gdk::RGBA::new(1.0, 0.84, 0.3, 0.95);
+            let text_color = gdk::RGBA::new(0.1, 0.1, 0.1, 1.0);
+            let padding = 6.0;
+
+            // Regions sharing a corner get their hints side by side
+            let mut placed: Vec<(f32, f32, f32)> = Vec::new();
+            for target in hints.visible_targets() {
+                // Letters already typed are shown dimmed
+                let markup = format!(
+                    "<b><span alpha=\"50%\">{}</span>{}</b>",
+                    hints.typed,
+                    &target.hint[hints.typed.len()..]
+                );
+                let layout = self.obj().create_pango_layout(None);
+                layout.set_markup(&markup);
+                let (text_width, text_height) = layout.pixel_size();
+                let box_width = text_width as f32 + padding * 2.0;
+                let box_height = text_height as f32 + padding;
+
+                let mut x = target.rect.x + 8.0;
+                let y = target.rect.y + 8.0;
+                while let Some(&(px, _, pw)) = placed
+                    .iter()
+                    .find(|&&(px, py, pw)| py == y && x < px + pw && px < x + box_width)
+                {
+                    x = px + pw + 4.0;
+                }
+                placed.push((x, y, box_width));
+
+                let bg_rect = graphene::Rect::new(x, y, box_width, box_height);
+                snapshot.push_rounded_clip(&gsk::RoundedRect::from_rect(bg_rect, 4.0));
+                snapshot.append_color(&bg_color, &bg_rect);
+                snapshot.pop();
+
+                snapshot.save();
+                snapshot.translate(&graphene::Point::new(x + padding, y + padding / 2.0));
+                snapshot.append_layout(&layout, &text_color);
+                snapshot.restore();
+            }
+        }
+
+        /// Draw predefined regions as clickable/highlightable areas. The
+        /// hovered region is drawn on top with its label, followed by its
+        /// position in the stack under the cursor if regions overlap.
         fn draw_predefined_regions(
             &self,
             snapshot: &gtk4::Snapshot,
-            regions: &[Rect],
+            regions: &[PredefinedRegion],
             hovered_region: Option<usize>,
+            stack_position: Option<(usize, usize)>,
         ) {
             if regions.is_empty() {
                 return;
@@ -550,22 +1101,7 @@ mod imp {
             let hover_fill_color = gdk::RGBA::new(1.0, 1.0, 1.0, 0.15);
             let border_width = 2.0;
 
-            for (i, region) in regions.iter().enumerate() {
-                let is_hovered = hovered_region == Some(i);
-                let border_color = if is_hovered {
-                    &hover_border_color
-                } else {
-                    &normal_border_color
-                };
-
-                // Draw hover fill if this region is hovered
-                if is_hovered {
-                    let fill_rect =
-                        graphene::Rect::new(region.x, region.y, region.width, region.height);
-                    snapshot.append_color(&hover_fill_color, &fill_rect);
-                }
-
-                // Draw border
+            let draw_border = |region: &Rect, border_color: &gdk::RGBA| {
                 // Top border
                 snapshot.append_color(
                     border_color,
@@ -596,6 +1132,33 @@ mod imp {
                         region.height,
                     ),
                 );
+            };
+
+            for (i, region) in regions.iter().enumerate() {
+                if hovered_region != Some(i) {
+                    draw_border(&region.rect, &normal_border_color);
+                }
+            }
+
+            // Draw the hovered region last so nested regions don't cover it
+            let Some(hovered) = hovered_region.and_then(|i| regions.get(i)) else {
+                return;
+            };
+            let region = &hovered.rect;
+            let fill_rect = graphene::Rect::new(region.x, region.y, region.width, region.height);
+            snapshot.append_color(&hover_fill_color, &fill_rect);
+            draw_border(region, &hover_border_color);
+
+            let mut text = hovered.label.clone().unwrap_or_default();
+            if let Some((position, count)) = stack_position {
+                if !text.is_empty() {
+                    text.push_str("  ");
+                }
+                text.push_str(&format!("{}/{}", position, count));
+            }
+            if !text.is_empty() {
+                let height = self.screen_height.get();
+                self.draw_label(snapshot, &text, region.x, region.y + 28.0, height);
             }
         }
     }
@@ -630,15 +1193,354 @@ impl Canvas {
         imp.screen_height.set(height);
 
         // Initialize selection with screen dimensions
-        *imp.selection.borrow_mut() = Selection::new(width, height);
+        let pixels = Pixels::from_pixbuf(pixbuf);
+        let edge_map = EdgeMap::new(pixels.width, pixels.height, pixels.luma_plane());
+        let mut selection = Selection::new(width, height);
+        selection.edge_map = Some(Rc::new(edge_map));
+        *imp.selection.borrow_mut() = selection;
+        *imp.pixels.borrow_mut() = Some(pixels);
 
         self.queue_draw();
     }
 
     /// Set predefined regions for quick selection
-    pub fn set_predefined_regions(&self, regions: Vec<Rect>) {
+    pub fn set_predefined_regions(&self, regions: Vec<PredefinedRegion>) {
+        self.imp()
+            .selection
+            .borrow_mut()
+            .set_predefined_regions(regions);
+        self.queue_draw();
+    }
+
+    /// Predefined regions offered for quick selection
+    pub fn predefined_regions(&self) -> Vec<PredefinedRegion> {
+        self.imp().selection.borrow().predefined_regions.clone()
+    }
+
+    /// The current selection rectangle, if any
+    pub fn selection_rect(&self) -> Option<Rect> {
+        self.imp()
+            .selection
+            .borrow()
+            .rect
+            .map(|rect| rect.normalized())
+    }
+
+    /// Find rectangular UI elements in the screenshot on a worker thread and
+    /// offer them, largest first, ahead of `regions` once found
+    pub fn detect_regions(&self, regions: Vec<PredefinedRegion>) {
+        let Some(pixels) = self.imp().pixels.borrow().clone() else {
+            self.set_predefined_regions(regions);
+            return;
+        };
+        let canvas_weak = self.downgrade();
+        glib::spawn_future_local(async move {
+            let detected = gio::spawn_blocking(move || detect::detect_regions(&pixels))
+                .await
+                .unwrap_or_default();
+            if let Some(canvas) = canvas_weak.upgrade() {
+                let mut detected: Vec<_> = detected
+                    .into_iter()
+                    .map(|rect| PredefinedRegion {
+                        rect,
+                        label: None,
+                        app: None,
+                    })
+                    .collect();
+                detected.extend(regions);
+                canvas.set_predefined_regions(detected);
+            }
+        });
+    }
+
+    /// Set the areas covered by each output, used as snap targets
+    pub fn set_output_regions(&self, regions: Vec<Rect>) {
+        self.imp().selection.borrow_mut().output_regions = regions;
+    }
+
+    /// Set the available selection size presets
+    pub fn set_size_presets(&self, presets: Vec<SizePreset>) {
+        let mut selection = self.imp().selection.borrow_mut();
+        selection.size_presets = presets;
+        selection.active_preset = None;
+    }
+
+    /// Activate a size preset by index, or go back to free selection with None
+    pub fn set_active_preset(&self, index: Option<usize>) {
+        let changed = self.imp().selection.borrow_mut().set_active_preset(index);
+        if changed {
+            self.queue_draw();
+            self.notify_selection_change();
+        }
+    }
+
+    /// Enable or disable snapping of selection edges
+    pub fn set_edge_snapping(&self, enabled: bool) {
+        self.imp().selection.borrow_mut().snap_to_edges = enabled;
+    }
+
+    /// Toggle snapping of selection edges
+    pub fn toggle_edge_snapping(&self) {
+        let mut selection = self.imp().selection.borrow_mut();
+        selection.snap_to_edges = !selection.snap_to_edges;
+    }
+
+    /// Set the named regions offered in the quick-pick menu
+    pub fn set_named_regions(&self, regions: Vec<(String, Rect)>) {
+        let imp = self.imp();
+        if let Some(popover) = imp.region_menu.take() {
+            popover.unparent();
+        }
+
+        if !regions.is_empty() {
+            let popover = self.build_region_menu(&regions);
+            popover.set_parent(self);
+            *imp.region_menu.borrow_mut() = Some(popover);
+        }
+        *imp.named_regions.borrow_mut() = regions;
+    }
+
+    /// Build the quick-pick menu with one entry per named region
+    fn build_region_menu(&self, regions: &[(String, Rect)]) -> gtk4::Popover {
+        let popover = gtk4::Popover::new();
+        popover.set_has_arrow(false);
+        let list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+
+        for (i, (name, _)) in regions.iter().enumerate() {
+            let item = gtk4::Button::with_label(name);
+            item.add_css_class("flat");
+            if let Some(child) = item.child() {
+                child.set_halign(gtk4::Align::Start);
+            }
+
+            let canvas_weak = self.downgrade();
+            let popover_weak = popover.downgrade();
+            item.connect_clicked(move |_| {
+                if let Some(popover) = popover_weak.upgrade() {
+                    popover.popdown();
+                }
+                if let Some(canvas) = canvas_weak.upgrade() {
+                    canvas.select_named_region(i);
+                }
+            });
+
+            // Highlight the region on screen while its entry is hovered
+            let hover = EventControllerMotion::new();
+            let canvas_weak = self.downgrade();
+            hover.connect_enter(move |_, _, _| {
+                if let Some(canvas) = canvas_weak.upgrade() {
+                    canvas.imp().highlighted_named_region.set(Some(i));
+                    canvas.queue_draw();
+                }
+            });
+            item.add_controller(hover);
+
+            list.append(&item);
+        }
+
+        let canvas_weak = self.downgrade();
+        popover.connect_closed(move |_| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.imp().highlighted_named_region.set(None);
+                canvas.queue_draw();
+            }
+        });
+
+        popover.set_child(Some(&list));
+        popover
+    }
+
+    /// Open the named region quick-pick menu at the cursor
+    pub fn show_region_menu(&self) {
+        let imp = self.imp();
+        if let Some(ref popover) = *imp.region_menu.borrow() {
+            let x = imp.cursor_x.get() as i32;
+            let y = imp.cursor_y.get() as i32;
+            popover.set_pointing_to(Some(&gdk::Rectangle::new(x, y, 1, 1)));
+            popover.popup();
+        }
+        self.queue_draw();
+    }
+
+    /// Turn magic wand mode on or off
+    pub fn toggle_wand_mode(&self) {
+        self.end_text_editing();
+        let imp = self.imp();
+        imp.wand_mode.set(!imp.wand_mode.get());
+        if imp.wand_mode.get() {
+            imp.annotations.borrow_mut().tool = None;
+        }
+        imp.wand_seed.set(None);
+        let cursor_name = if imp.wand_mode.get() {
+            "crosshair"
+        } else {
+            let selection = imp.selection.borrow();
+            selection.cursor_for_position(imp.cursor_x.get(), imp.cursor_y.get())
+        };
+        self.set_cursor_by_name(cursor_name);
+        self.queue_draw();
+    }
+
+    pub fn set_wand_tolerance(&self, tolerance: u8) {
+        self.imp().wand_tolerance.set(tolerance);
+    }
+
+    /// Change the magic wand tolerance and redo the last fill with it
+    pub fn adjust_wand_tolerance(&self, increase: bool) {
+        let imp = self.imp();
+        if !imp.wand_mode.get() {
+            return;
+        }
+        let tolerance = imp.wand_tolerance.get();
+        imp.wand_tolerance.set(if increase {
+            tolerance.saturating_add(wand::TOLERANCE_STEP)
+        } else {
+            tolerance.saturating_sub(wand::TOLERANCE_STEP)
+        });
+
+        if let Some((x, y, keep_mask)) = imp.wand_seed.get() {
+            self.wand_select(x, y, keep_mask);
+        }
+        self.queue_draw();
+    }
+
+    /// Flood-fill from a point and select the filled area, either as its
+    /// bounding box or as a masked shape
+    fn wand_select(&self, x: i32, y: i32, keep_mask: bool) {
+        let imp = self.imp();
+        let mask = match *imp.pixels.borrow() {
+            Some(ref pixels) => wand::flood_fill(pixels, x, y, imp.wand_tolerance.get()),
+            None => None,
+        };
+        let Some(mask) = mask else {
+            return;
+        };
+        imp.wand_seed.set(Some((x, y, keep_mask)));
+
+        let mut selection = imp.selection.borrow_mut();
+        selection.set_shape(SelectionShape::Rect);
+        if keep_mask {
+            selection.set_mask(mask);
+        } else {
+            selection.set_rect(Some(mask.bounds()));
+        }
+        drop(selection);
+
+        self.queue_draw();
+        self.notify_selection_change();
+    }
+
+    /// Get the mask of a non-rectangular selection
+    pub fn get_crop_mask(&self) -> Option<Rc<Mask>> {
+        self.imp().selection.borrow().crop_mask()
+    }
+
+    /// Switch to the given selection shape, or back to a rectangle if it is
+    /// already active
+    pub fn toggle_selection_shape(&self, shape: SelectionShape) {
         let mut selection = self.imp().selection.borrow_mut();
-        selection.predefined_regions = regions;
+        let shape = if selection.shape == shape {
+            SelectionShape::Rect
+        } else {
+            shape
+        };
+        selection.set_shape(shape);
+        drop(selection);
+        self.queue_draw();
+        self.notify_selection_change();
+    }
+
+    /// Finish the polygon being placed
+    pub fn close_polygon(&self) -> bool {
+        let closed = self.imp().selection.borrow_mut().close_polygon();
+        if closed {
+            self.queue_draw();
+            self.notify_selection_change();
+        }
+        closed
+    }
+
+    /// Remove the last vertex of the polygon being placed
+    pub fn remove_polygon_vertex(&self) -> bool {
+        let removed = self.imp().selection.borrow_mut().remove_polygon_vertex();
+        if removed {
+            self.queue_draw();
+            self.notify_selection_change();
+        }
+        removed
+    }
+
+    /// Show letter hints over every predefined region and output. Returns
+    /// false if there is nothing to hint.
+    pub fn show_hints(&self) -> bool {
+        let selection = self.imp().selection.borrow();
+        let regions = selection
+            .predefined_regions
+            .iter()
+            .map(|r| r.rect)
+            .chain(selection.output_regions.iter().copied());
+        let hints = Hints::new(regions);
+        drop(selection);
+
+        if hints.targets.is_empty() {
+            return false;
+        }
+        *self.imp().hints.borrow_mut() = Some(hints);
+        self.queue_draw();
+        true
+    }
+
+    /// Leave hint mode
+    pub fn hide_hints(&self) {
+        if self.imp().hints.borrow_mut().take().is_some() {
+            self.queue_draw();
+        }
+    }
+
+    pub fn hints_active(&self) -> bool {
+        self.imp().hints.borrow().is_some()
+    }
+
+    /// Type a letter of a hint. A completed hint selects its region and
+    /// leaves hint mode.
+    pub fn type_hint(&self, c: char) -> HintInput {
+        let input = match *self.imp().hints.borrow_mut() {
+            Some(ref mut hints) => hints.type_char(c),
+            None => return HintInput::NoMatch,
+        };
+        match input {
+            HintInput::Matched(rect) => {
+                self.hide_hints();
+                self.imp().selection.borrow_mut().set_rect(Some(rect));
+                self.crop_window_shadow();
+                self.queue_draw();
+                self.notify_selection_change();
+            }
+            HintInput::Pending => self.queue_draw(),
+            HintInput::NoMatch => {}
+        }
+        input
+    }
+
+    /// Remove the last typed hint letter
+    pub fn hint_backspace(&self) {
+        if let Some(ref mut hints) = *self.imp().hints.borrow_mut() {
+            hints.backspace();
+        }
+        self.queue_draw();
+    }
+
+    /// Select a named region by index
+    pub fn select_named_region(&self, index: usize) {
+        let rect = self
+            .imp()
+            .named_regions
+            .borrow()
+            .get(index)
+            .map(|(_, r)| *r);
+        if let Some(rect) = rect {
+            self.set_selection(rect);
+        }
     }
 
     /// Set callback for selection changes
@@ -711,8 +1613,25 @@ impl Canvas {
         drag.set_button(gdk::BUTTON_PRIMARY);
 
         let canvas_weak = self.downgrade();
-        drag.connect_drag_begin(move |_, x, y| {
+        drag.connect_drag_begin(move |gesture, x, y| {
             if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.hide_hints();
+
+                // In magic wand mode a click selects by color, with Shift
+                // keeping the filled shape instead of its bounding box
+                if canvas.imp().wand_mode.get() {
+                    let keep_mask = gesture
+                        .current_event_state()
+                        .contains(gdk::ModifierType::SHIFT_MASK);
+                    canvas.wand_select(x as i32, y as i32, keep_mask);
+                    return;
+                }
+
+                // With an annotation tool, drags inside the selection draw
+                if canvas.start_annotation(x as f32, y as f32, pen_pressure(gesture)) {
+                    return;
+                }
+
                 let mut selection = canvas.imp().selection.borrow_mut();
 
                 // If no selection exists and clicking on a predefined region, select it
@@ -720,13 +1639,22 @@ impl Canvas {
                     if let Some(index) = selection.find_predefined_region_at(x as f32, y as f32) {
                         selection.select_predefined_region(index);
                         drop(selection);
+                        canvas.crop_window_shadow();
                         canvas.queue_draw();
                         canvas.notify_selection_change();
                         return;
                     }
                 }
 
-                selection.start_drag(x as f32, y as f32);
+                // Ctrl+drag adds another area instead of replacing the selection
+                let adding = gesture
+                    .current_event_state()
+                    .contains(gdk::ModifierType::CONTROL_MASK);
+                if adding {
+                    selection.start_drag_adding(x as f32, y as f32);
+                } else {
+                    selection.start_drag(x as f32, y as f32);
+                }
                 let cursor_name = selection.cursor_for_position(x as f32, y as f32);
                 drop(selection);
 
@@ -743,8 +1671,28 @@ impl Canvas {
                 let x = start_x + offset_x;
                 let y = start_y + offset_y;
 
+                let state = gesture.current_event_state();
+                let modifiers = DragModifiers {
+                    keep_aspect: state.contains(gdk::ModifierType::SHIFT_MASK),
+                    from_center: state.contains(gdk::ModifierType::ALT_MASK),
+                };
+
+                let mut annotations = canvas.imp().annotations.borrow_mut();
+                if annotations.drawing.is_some() || annotations.editing.is_some() {
+                    annotations.update(
+                        x as f32,
+                        y as f32,
+                        modifiers.keep_aspect,
+                        pen_pressure(gesture),
+                    );
+                    drop(annotations);
+                    canvas.queue_draw();
+                    return;
+                }
+                drop(annotations);
+
                 let mut selection = canvas.imp().selection.borrow_mut();
-                selection.update_drag(x as f32, y as f32);
+                selection.update_drag(x as f32, y as f32, modifiers);
                 drop(selection);
                 canvas.queue_draw();
                 canvas.notify_selection_change();
@@ -754,6 +1702,14 @@ impl Canvas {
         let canvas_weak = self.downgrade();
         drag.connect_drag_end(move |gesture, _, _| {
             if let Some(canvas) = canvas_weak.upgrade() {
+                let mut annotations = canvas.imp().annotations.borrow_mut();
+                if annotations.finish() || annotations.editing.is_some() {
+                    drop(annotations);
+                    canvas.queue_draw();
+                    return;
+                }
+                drop(annotations);
+
                 let mut selection = canvas.imp().selection.borrow_mut();
                 selection.end_drag();
 
@@ -770,6 +1726,19 @@ impl Canvas {
 
         self.add_controller(drag);
 
+        // Right-click deletes a step marker, renumbering the others
+        let right_click = GestureClick::new();
+        right_click.set_button(gdk::BUTTON_SECONDARY);
+        let canvas_weak = self.downgrade();
+        right_click.connect_pressed(move |gesture, _, x, y| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.remove_step_at(x as f32, y as f32) {
+                    gesture.set_state(gtk4::EventSequenceState::Claimed);
+                }
+            }
+        });
+        self.add_controller(right_click);
+
         // Motion controller for cursor updates
         let motion = EventControllerMotion::new();
         let canvas_weak = self.downgrade();
@@ -790,8 +1759,14 @@ impl Canvas {
                 let selection = imp.selection.borrow();
 
                 // Use pointer cursor when hovering over a predefined region
-                let cursor_name = if selection.hovered_region.is_some() && selection.rect.is_none()
-                {
+                let annotating = imp.annotations.borrow().tool.is_some()
+                    && selection
+                        .selected_areas()
+                        .iter()
+                        .any(|area| area.contains(x as f32, y as f32));
+                let cursor_name = if imp.wand_mode.get() || annotating {
+                    "crosshair"
+                } else if selection.hovered_region.is_some() && selection.rect.is_none() {
                     "pointer"
                 } else {
                     selection.cursor_for_position(x as f32, y as f32)
@@ -827,6 +1802,46 @@ impl Canvas {
 
         self.add_controller(motion);
 
+        // Scroll to cycle through overlapping predefined regions
+        let scroll = EventControllerScroll::new(
+            EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE,
+        );
+        let canvas_weak = self.downgrade();
+        scroll.connect_scroll(move |_, _, dy| match canvas_weak.upgrade() {
+            Some(canvas) if dy != 0.0 && canvas.cycle_predefined_region(dy > 0.0) => {
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        });
+        self.add_controller(scroll);
+
+        // Typing into text boxes. Keys go through the input method so compose
+        // sequences and IMEs work, and only while a text box is being edited.
+        let imp = self.imp();
+        imp.im_context.set_client_widget(Some(self));
+        let canvas_weak = self.downgrade();
+        imp.im_context.connect_commit(move |_, text| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.imp().annotations.borrow_mut().insert_text(text);
+                canvas.text_changed();
+            }
+        });
+        let canvas_weak = self.downgrade();
+        imp.im_context.connect_preedit_changed(move |im| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                let (preedit, _, _) = im.preedit_string();
+                canvas.imp().annotations.borrow_mut().preedit = preedit.to_string();
+                canvas.text_changed();
+            }
+        });
+        let canvas_weak = self.downgrade();
+        imp.text_keys
+            .connect_key_pressed(move |_, key, _, modifier| match canvas_weak.upgrade() {
+                Some(canvas) => canvas.text_key_pressed(key, modifier),
+                None => glib::Propagation::Proceed,
+            });
+        self.add_controller(imp.text_keys.clone());
+
         // Set cursor_inside to true initially since the window covers the whole screen
         // and cursor is always "inside" when the app launches
         self.imp().cursor_inside.set(true);
@@ -860,6 +1875,419 @@ impl Canvas {
         self.imp().selection.borrow().get_crop_region()
     }
 
+    pub fn set_trim_tolerance(&self, tolerance: u8) {
+        self.imp().trim_tolerance.set(tolerance);
+    }
+
+    /// Shrink the selection to its content by removing borders of uniform
+    /// color. Returns false if there was nothing to trim.
+    pub fn trim_selection(&self) -> bool {
+        let imp = self.imp();
+        let pixels = imp.pixels.borrow();
+        let mut selection = imp.selection.borrow_mut();
+        // Trimming the bounds of a polygon or lasso would distort it
+        if selection.shape.has_outline() || selection.polygon_open {
+            return false;
+        }
+        let (Some(pixels), Some(region)) = (pixels.as_ref(), selection.get_crop_region()) else {
+            return false;
+        };
+        let Some((x, y, w, h)) = trim::trim(pixels, region, imp.trim_tolerance.get()) else {
+            return false;
+        };
+        selection.set_rect(Some(Rect::new(x as f32, y as f32, w as f32, h as f32)));
+        drop(selection);
+        drop(pixels);
+
+        self.queue_draw();
+        self.notify_selection_change();
+        true
+    }
+
+    pub fn set_window_shadow(&self, default: ShadowCrop, apps: Vec<(String, ShadowCrop)>) {
+        self.imp().window_shadow.set(default);
+        *self.imp().app_window_shadows.borrow_mut() = apps;
+    }
+
+    /// The drop shadow around the window in a rectangular selection
+    fn window_shadow(&self) -> Option<Shadow> {
+        let imp = self.imp();
+        let selection = imp.selection.borrow();
+        if selection.shape.has_outline() || selection.polygon_open {
+            return None;
+        }
+        let pixels = imp.pixels.borrow();
+        let (Some(pixels), Some(region)) = (pixels.as_ref(), selection.get_crop_region()) else {
+            return None;
+        };
+        shadow::detect(pixels, region)
+    }
+
+    /// Crop a window just picked from the predefined regions with or without
+    /// its shadow, as configured for its app
+    fn crop_window_shadow(&self) {
+        let imp = self.imp();
+        let mode = {
+            let selection = imp.selection.borrow();
+            let app = selection
+                .rect
+                .and_then(|rect| selection.predefined_regions.iter().find(|r| r.rect == rect))
+                .and_then(|r| r.app.clone());
+            let apps = imp.app_window_shadows.borrow();
+            app.and_then(|app| apps.iter().find(|(a, _)| a.eq_ignore_ascii_case(&app)))
+                .map_or(imp.window_shadow.get(), |(_, mode)| *mode)
+        };
+        if mode == ShadowCrop::Keep {
+            return;
+        }
+        if let Some((x, y, w, h)) = self.window_shadow().and_then(|s| s.crop(mode)) {
+            let rect = Rect::new(x as f32, y as f32, w as f32, h as f32);
+            imp.selection.borrow_mut().set_rect(Some(rect));
+        }
+    }
+
+    /// Switch the selection between a window's content and the window with
+    /// its shadow. Returns false if there is no shadow around the selection.
+    pub fn toggle_window_shadow(&self) -> bool {
+        let Some(shadow) = self.window_shadow() else {
+            return false;
+        };
+        let (x, y, w, h) = if self.get_crop_region() == Some(shadow.tight) {
+            shadow.with_shadow
+        } else {
+            shadow.tight
+        };
+        self.set_selection(Rect::new(x as f32, y as f32, w as f32, h as f32));
+        true
+    }
+
+    /// Switch to an annotation tool, or back to editing the selection if it
+    /// is already active
+    pub fn toggle_annotation_tool(&self, tool: AnnotationTool) {
+        if self.annotation_tool() == Some(tool) {
+            self.set_annotation_tool(None);
+        } else {
+            self.set_annotation_tool(Some(tool));
+        }
+    }
+
+    /// Switch to blurring, then pixelating, then filling redactions, then
+    /// back to editing the selection
+    pub fn cycle_redact_tool(&self) {
+        let next = match self.annotation_tool() {
+            Some(AnnotationTool::Redact(style)) => style.next(),
+            _ => Some(RedactStyle::Blur),
+        };
+        self.set_annotation_tool(next.map(AnnotationTool::Redact));
+    }
+
+    fn set_annotation_tool(&self, tool: Option<AnnotationTool>) {
+        self.end_text_editing();
+        let imp = self.imp();
+        imp.annotations.borrow_mut().tool = tool;
+        if tool.is_some() {
+            imp.wand_mode.set(false);
+        }
+        self.queue_draw();
+    }
+
+    pub fn annotation_tool(&self) -> Option<AnnotationTool> {
+        self.imp().annotations.borrow().tool
+    }
+
+    /// Set the colors offered for annotations and the initial stroke width
+    pub fn set_annotation_style(&self, palette: Vec<[u8; 3]>, width: f32) {
+        let mut annotations = self.imp().annotations.borrow_mut();
+        annotations.palette = palette;
+        annotations.color = 0;
+        annotations.width = width;
+    }
+
+    /// Use the next palette color for new annotations
+    pub fn cycle_annotation_color(&self) {
+        self.imp().annotations.borrow_mut().cycle_color();
+        self.queue_draw();
+    }
+
+    /// Use a palette color for new annotations
+    pub fn set_annotation_color(&self, index: usize) {
+        let mut annotations = self.imp().annotations.borrow_mut();
+        if index < annotations.palette.len() {
+            annotations.color = index;
+        }
+        drop(annotations);
+        self.queue_draw();
+    }
+
+    /// Make the stroke of new annotations thicker or thinner, or the text or
+    /// step markers larger or smaller with their tools
+    pub fn adjust_annotation_width(&self, increase: bool) {
+        let mut annotations = self.imp().annotations.borrow_mut();
+        if annotations.tool == Some(AnnotationTool::Text) {
+            annotations.adjust_font_size(increase);
+            drop(annotations);
+            self.text_changed();
+            return;
+        }
+        if annotations.tool == Some(AnnotationTool::Step) {
+            annotations.adjust_step_radius(increase);
+        } else {
+            annotations.adjust_width(increase);
+        }
+        drop(annotations);
+        self.queue_draw();
+    }
+
+    /// Set the radius and look of new step markers
+    pub fn set_step_style(&self, radius: f32, style: StepStyle) {
+        let mut annotations = self.imp().annotations.borrow_mut();
+        annotations.step_radius = radius;
+        annotations.step_style = style;
+    }
+
+    /// Delete the step marker at a point while the step tool is active.
+    /// Returns false if there is none.
+    pub fn remove_step_at(&self, x: f32, y: f32) -> bool {
+        if self.annotation_tool() != Some(AnnotationTool::Step) {
+            return false;
+        }
+        if !self.imp().annotations.borrow_mut().remove_step_at(x, y) {
+            return false;
+        }
+        self.queue_draw();
+        true
+    }
+
+    /// Set the font family, size and background box of new text
+    pub fn set_text_style(&self, font: &str, size: f32, background: bool) {
+        let mut annotations = self.imp().annotations.borrow_mut();
+        annotations.font = font.to_string();
+        annotations.font_size = size;
+        annotations.text_background = background;
+    }
+
+    /// Font family, size and background box of new text
+    pub fn text_style(&self) -> (String, f32, bool) {
+        let annotations = self.imp().annotations.borrow();
+        (
+            annotations.font.clone(),
+            annotations.font_size,
+            annotations.text_background,
+        )
+    }
+
+    /// Set the font family of new text and of the text box being edited
+    pub fn set_text_font(&self, font: &str) {
+        self.imp().annotations.borrow_mut().set_font(font);
+        self.text_changed();
+    }
+
+    /// Set the size of new text and of the text box being edited
+    pub fn set_text_size(&self, size: f32) {
+        self.imp().annotations.borrow_mut().set_font_size(size);
+        self.text_changed();
+    }
+
+    /// Set whether new text, and the text box being edited, get a background
+    pub fn set_text_background(&self, background: bool) {
+        self.imp()
+            .annotations
+            .borrow_mut()
+            .set_text_background(background);
+        self.text_changed();
+    }
+
+    /// Whether a text box is being typed into
+    pub fn editing_text(&self) -> bool {
+        self.imp().annotations.borrow().editing.is_some()
+    }
+
+    /// Route keys through the input method into the current text box
+    fn begin_text_editing(&self) {
+        let imp = self.imp();
+        imp.text_keys.set_im_context(Some(&imp.im_context));
+        imp.im_context.focus_in();
+        self.grab_focus();
+        self.text_changed();
+    }
+
+    /// Stop typing into the current text box, dropping it if it is empty.
+    /// Returns true if it was dropped.
+    pub fn end_text_editing(&self) -> bool {
+        let imp = self.imp();
+        let Some(kept) = imp.annotations.borrow_mut().end_text() else {
+            return false;
+        };
+        imp.im_context.reset();
+        imp.im_context.focus_out();
+        imp.text_keys.set_im_context(None::<&gtk4::IMContext>);
+        self.queue_draw();
+        !kept
+    }
+
+    /// Keys not consumed by the input method while typing: Enter starts a
+    /// new line, Backspace deletes and Esc or Ctrl+Enter finish the text.
+    /// Other shortcuts with Ctrl still apply.
+    fn text_key_pressed(&self, key: gdk::Key, modifier: gdk::ModifierType) -> glib::Propagation {
+        if !self.editing_text() {
+            return glib::Propagation::Proceed;
+        }
+        let enter = key == gdk::Key::Return || key == gdk::Key::KP_Enter;
+        if modifier.contains(gdk::ModifierType::CONTROL_MASK) {
+            if !enter {
+                return glib::Propagation::Proceed;
+            }
+            self.end_text_editing();
+        } else if key == gdk::Key::Escape {
+            self.end_text_editing();
+        } else if enter {
+            self.imp().annotations.borrow_mut().insert_text("\n");
+            self.text_changed();
+        } else if key == gdk::Key::BackSpace {
+            self.imp().annotations.borrow_mut().delete_char();
+            self.text_changed();
+        }
+        glib::Propagation::Stop
+    }
+
+    /// Insertion point of the text box being edited, after any text being
+    /// composed
+    fn text_caret(&self) -> Option<Rect> {
+        let annotations = self.imp().annotations.borrow();
+        let mut text = annotations.editing_text()?.clone();
+        text.text.push_str(&annotations.preedit);
+        Some(text.caret(&self.pango_context()))
+    }
+
+    /// Redraw after the text being edited changed, and keep the input
+    /// method's candidate window next to the caret
+    fn text_changed(&self) {
+        if let Some(caret) = self.text_caret() {
+            let area = gdk::Rectangle::new(
+                caret.x as i32,
+                caret.y as i32,
+                1,
+                caret.height.ceil() as i32,
+            );
+            self.imp().im_context.set_cursor_location(&area);
+        }
+        self.queue_draw();
+    }
+
+    /// Finished annotations, in screen coordinates
+    pub fn get_annotations(&self) -> Vec<Annotation> {
+        self.imp().annotations.borrow().items.clone()
+    }
+
+    /// Start drawing an annotation if a tool is active and the point is in
+    /// a selected area. With the text tool, clicking a text box edits it and
+    /// clicking elsewhere places a new one.
+    fn start_annotation(&self, x: f32, y: f32, pressure: Option<f32>) -> bool {
+        self.end_text_editing();
+        let imp = self.imp();
+        let inside = imp
+            .selection
+            .borrow()
+            .selected_areas()
+            .iter()
+            .any(|area| area.contains(x, y));
+        if !inside {
+            return false;
+        }
+
+        let mut annotations = imp.annotations.borrow_mut();
+        if annotations.tool == Some(AnnotationTool::Text) {
+            let context = self.pango_context();
+            let hit = annotations.items.iter().rposition(|annotation| {
+                matches!(&annotation.shape,
+                    AnnotationShape::Text(text) if text.bounds(&context).contains(x, y))
+            });
+            match hit {
+                Some(index) => annotations.edit_text(index),
+                None => annotations.start(x, y, None),
+            };
+            drop(annotations);
+            self.begin_text_editing();
+            return true;
+        }
+        if !annotations.start(x, y, pressure) {
+            return false;
+        }
+        drop(annotations);
+        self.queue_draw();
+        true
+    }
+
+    /// Get all selected areas, including those added with Ctrl+drag
+    pub fn get_crop_regions(&self) -> Vec<(i32, i32, i32, i32)> {
+        self.imp().selection.borrow().get_crop_regions()
+    }
+
+    /// Set how several selected areas are exported, and the background
+    /// behind them when they are combined into one image
+    pub fn set_multi_export(&self, mode: MultiExport, background: Option<[u8; 3]>) {
+        self.imp().multi_export.set(mode);
+        self.imp().multi_background.set(background);
+    }
+
+    pub fn multi_export(&self) -> (MultiExport, Option<[u8; 3]>) {
+        let imp = self.imp();
+        (imp.multi_export.get(), imp.multi_background.get())
+    }
+
+    /// Set the format of saved images and the callouts added to them
+    pub fn set_export(&self, format: ExportFormat, callouts: Callouts) {
+        self.imp().export_format.set(format);
+        *self.imp().callouts.borrow_mut() = callouts;
+    }
+
+    pub fn export(&self) -> (ExportFormat, Callouts) {
+        let imp = self.imp();
+        (imp.export_format.get(), imp.callouts.borrow().clone())
+    }
+
+    /// Set the beautify presets, the one to use and whether exports are
+    /// beautified
+    pub fn set_beautify(&self, presets: Vec<Preset>, preset: usize, enabled: bool) {
+        let imp = self.imp();
+        imp.beautify_preset
+            .set(preset.min(presets.len().saturating_sub(1)));
+        *imp.beautify_presets.borrow_mut() = presets;
+        imp.beautify_enabled.set(enabled);
+    }
+
+    pub fn beautify_presets(&self) -> Vec<Preset> {
+        self.imp().beautify_presets.borrow().clone()
+    }
+
+    pub fn beautify_preset(&self) -> usize {
+        self.imp().beautify_preset.get()
+    }
+
+    pub fn set_beautify_preset(&self, index: usize) {
+        if index < self.imp().beautify_presets.borrow().len() {
+            self.imp().beautify_preset.set(index);
+        }
+    }
+
+    pub fn beautify_enabled(&self) -> bool {
+        self.imp().beautify_enabled.get()
+    }
+
+    pub fn set_beautify_enabled(&self, enabled: bool) {
+        self.imp().beautify_enabled.set(enabled);
+    }
+
+    /// The style exports are decorated with, if beautify is on
+    pub fn beautify(&self) -> Option<Style> {
+        let imp = self.imp();
+        if !imp.beautify_enabled.get() {
+            return None;
+        }
+        let presets = imp.beautify_presets.borrow();
+        presets.get(imp.beautify_preset.get()).map(|p| p.style)
+    }
+
     /// Select the entire screen
     pub fn select_all(&self) {
         let imp = self.imp();
@@ -867,12 +2295,81 @@ impl Canvas {
         let height = imp.screen_height.get();
 
         let mut selection = imp.selection.borrow_mut();
-        selection.rect = Some(crate::selection::Rect::new(0.0, 0.0, width, height));
+        selection.set_rect(Some(crate::selection::Rect::new(0.0, 0.0, width, height)));
         drop(selection);
 
         self.queue_draw();
         self.notify_selection_change();
     }
+
+    /// Replace the selection with the given rect
+    pub fn set_selection(&self, rect: Rect) {
+        self.imp().selection.borrow_mut().set_rect(Some(rect));
+        self.queue_draw();
+        self.notify_selection_change();
+    }
+
+    /// Move the selection by the given offset
+    pub fn nudge(&self, dx: f32, dy: f32) {
+        self.imp().selection.borrow_mut().nudge(dx, dy);
+        self.queue_draw();
+        self.notify_selection_change();
+    }
+
+    /// Undo the last selection edit, or the last annotation while an
+    /// annotation tool is active
+    pub fn undo(&self) {
+        // Undoing right after placing a text box only removes that box
+        if self.end_text_editing() {
+            return;
+        }
+        let mut annotations = self.imp().annotations.borrow_mut();
+        if annotations.tool.is_some() {
+            if annotations.undo() {
+                drop(annotations);
+                self.queue_draw();
+            }
+            return;
+        }
+        drop(annotations);
+
+        if self.imp().selection.borrow_mut().undo() {
+            self.queue_draw();
+            self.notify_selection_change();
+        }
+    }
+
+    /// Redo the last undone selection edit or annotation
+    pub fn redo(&self) {
+        self.end_text_editing();
+        let mut annotations = self.imp().annotations.borrow_mut();
+        if annotations.tool.is_some() {
+            if annotations.redo() {
+                drop(annotations);
+                self.queue_draw();
+            }
+            return;
+        }
+        drop(annotations);
+
+        if self.imp().selection.borrow_mut().redo() {
+            self.queue_draw();
+            self.notify_selection_change();
+        }
+    }
+
+    /// Hover the next larger (or smaller) predefined region under the
+    /// cursor. Returns false while a selection exists or if no regions
+    /// overlap under the cursor.
+    pub fn cycle_predefined_region(&self, forward: bool) -> bool {
+        let mut selection = self.imp().selection.borrow_mut();
+        if selection.rect.is_some() || !selection.cycle_hovered_region(forward) {
+            return false;
+        }
+        drop(selection);
+        self.queue_draw();
+        true
+    }
 }
 
 impl Default for Canvas {
@@ -880,3 +2377,119 @@ impl Default for Canvas {
         Self::new()
     }
 }
+
+/// Pressure of the stylus driving a gesture, if the device reports it
+fn pen_pressure(gesture: &GestureDrag) -> Option<f32> {
+    gesture
+        .current_event()
+        .and_then(|event| event.axis(gdk::AxisUse::Pressure))
+        .map(|pressure| pressure as f32)
+}
+
+/// Dim the parts of the bounds outside a rect, in 4 strips around it
+fn dim_around(snapshot: &gtk4::Snapshot, rect: Rect, bounds: &graphene::Rect, color: &gdk::RGBA) {
+    let (left, top) = (bounds.x(), bounds.y());
+    let (right, bottom) = (left + bounds.width(), top + bounds.height());
+    let rect_left = rect.x.clamp(left, right);
+    let rect_top = rect.y.clamp(top, bottom);
+    let rect_right = (rect.x + rect.width).clamp(left, right);
+    let rect_bottom = (rect.y + rect.height).clamp(top, bottom);
+
+    // Top strip
+    if rect_top > top {
+        let top_rect = graphene::Rect::new(left, top, bounds.width(), rect_top - top);
+        snapshot.append_color(color, &top_rect);
+    }
+
+    // Bottom strip
+    if rect_bottom < bottom {
+        let bottom_rect =
+            graphene::Rect::new(left, rect_bottom, bounds.width(), bottom - rect_bottom);
+        snapshot.append_color(color, &bottom_rect);
+    }
+
+    // Left strip (between top and bottom)
+    if rect_left > left {
+        let left_rect =
+            graphene::Rect::new(left, rect_top, rect_left - left, rect_bottom - rect_top);
+        snapshot.append_color(color, &left_rect);
+    }
+
+    // Right strip (between top and bottom)
+    if rect_right < right {
+        let right_rect = graphene::Rect::new(
+            rect_right,
+            rect_top,
+            right - rect_right,
+            rect_bottom - rect_top,
+        );
+        snapshot.append_color(color, &right_rect);
+    }
+}
+
+/// Zoom part of a pixbuf with nearest-neighbor scaling, obscuring the
+/// redactions first. The part is clamped to the pixbuf; returns the texture
+/// and where it goes relative to the zoomed corner of the unclamped part.
+fn zoomed_texture(
+    pixbuf: &Pixbuf,
+    (x, y, width, height): (i32, i32, i32, i32),
+    scale: i32,
+    redactions: &[Redaction],
+) -> Option<(graphene::Rect, gdk::Texture)> {
+    // Clamp to valid pixbuf bounds
+    let (left, top) = (x.max(0), y.max(0));
+    let (right, bottom) = (
+        (x + width).min(pixbuf.width()),
+        (y + height).min(pixbuf.height()),
+    );
+    if left >= right || top >= bottom {
+        return None;
+    }
+    let (width, height) = (right - left, bottom - top);
+
+    // Extract sub-region, redacted in a copy when a redaction overlaps it
+    let mut sub_pixbuf = pixbuf.new_subpixbuf(left, top, width, height);
+    let local: Vec<Redaction> = redactions
+        .iter()
+        .filter(|r| {
+            let (rx, ry, rw, rh) = r.region;
+            rx < right && ry < bottom && rx + rw > left && ry + rh > top
+        })
+        .map(|r| Redaction {
+            region: (r.region.0 - left, r.region.1 - top, r.region.2, r.region.3),
+            ..*r
+        })
+        .collect();
+    if !local.is_empty() {
+        // The screenshot pixbuf is always RGBA, see `Screenshot::capture`
+        let copy = sub_pixbuf.copy()?;
+        let stride = copy.rowstride() as usize;
+        let mut data = copy.read_pixel_bytes().to_vec();
+        for redaction in &local {
+            redaction.apply(&mut data, (width, height), stride);
+        }
+        sub_pixbuf = Pixbuf::from_bytes(
+            &glib::Bytes::from_owned(data),
+            gdk_pixbuf::Colorspace::Rgb,
+            true,
+            8,
+            width,
+            height,
+            stride as i32,
+        );
+    }
+
+    // Scale with nearest-neighbor, offset for parts clamped at the edges
+    let scaled = sub_pixbuf.scale_simple(
+        width * scale,
+        height * scale,
+        gdk_pixbuf::InterpType::Nearest,
+    )?;
+    let rect = graphene::Rect::new(
+        ((left - x) * scale) as f32,
+        ((top - y) * scale) as f32,
+        (width * scale) as f32,
+        (height * scale) as f32,
+    );
+    Some((rect, gdk::Texture::for_pixbuf(&scaled)))
+}
@@ -0,0 +1,275 @@
+//! Command line argument parsing
+
+use crate::selection::Rect;
+use crate::vector::{ExportFormat, MAX_BORDER};
+use std::path::PathBuf;
+
+/// Usage text printed for --help and on invalid arguments
+pub const USAGE: &str = "\
+Usage: waysnip [OPTIONS]
+       waysnip open FILE.waysnip
+
+Commands:
+  open FILE        Reopen a session saved with Ctrl+Shift+S, to change the
+                   selection on the capture it was taken from
+
+Options:
+  --last-region    Capture the last used region without showing the overlay
+                   and copy it to the clipboard
+  --region NAME    Capture a named region from the config the same way
+  --save           With --last-region or --region, save to ~/Pictures
+                   instead of copying
+  --hints          Start with letter hints shown over windows and outputs
+  --format FORMAT  Save as png, svg or pdf; SVG and PDF keep annotations and
+                   callouts as vectors over the embedded capture
+  --box AREA       With --save, outline AREA of the capture, given as
+                   \"X,Y WxH\" from its top-left corner; can be repeated
+  --caption TEXT   Add a caption below saved images
+  --border WIDTH   Add a border of WIDTH pixels around saved images
+  -h, --help       Show this help";
+
+/// Parsed command line arguments
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    /// Capture the last used region non-interactively
+    pub last_region: bool,
+    /// Capture a named region non-interactively
+    pub region: Option<String>,
+    /// Save non-interactive captures to a file instead of the clipboard
+    pub save: bool,
+    /// Start in hint mode
+    pub hints: bool,
+    /// Saved session to reopen instead of capturing the screen
+    pub open: Option<PathBuf>,
+    /// Format of saved images
+    pub format: Option<ExportFormat>,
+    /// Highlight boxes relative to a non-interactive capture
+    pub boxes: Vec<Rect>,
+    /// Caption below saved images
+    pub caption: Option<String>,
+    /// Border width around saved images
+    pub border: Option<f32>,
+    /// Print usage and exit
+    pub help: bool,
+}
+
+impl Args {
+    /// Parse arguments, not including the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--last-region" => parsed.last_region = true,
+                "--region" => {
+                    let name = args.next().ok_or("--region needs a region name")?;
+                    parsed.region = Some(name);
+                }
+                "--save" => parsed.save = true,
+                "--hints" => parsed.hints = true,
+                "-h" | "--help" => parsed.help = true,
+                "--format" => {
+                    let format = args.next().ok_or("--format needs png, svg or pdf")?;
+                    let format = ExportFormat::parse(&format)
+                        .ok_or_else(|| format!("Unknown format: {}", format))?;
+                    parsed.format = Some(format);
+                }
+                "--box" => {
+                    let rect = args.next().ok_or("--box needs an area")?;
+                    let rect = Rect::parse(&rect)
+                        .ok_or_else(|| format!("Invalid box \"{}\", expected \"X,Y WxH\"", rect))?;
+                    parsed.boxes.push(rect);
+                }
+                "--caption" => {
+                    let caption = args.next().ok_or("--caption needs a text")?;
+                    parsed.caption = Some(caption);
+                }
+                "--border" => {
+                    let width = args.next().ok_or("--border needs a width")?;
+                    let width = width
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|w| (0.0..=MAX_BORDER).contains(w))
+                        .ok_or_else(|| format!("Border must be 0-{} pixels", MAX_BORDER))?;
+                    parsed.border = Some(width);
+                }
+                "open" => {
+                    let path = args.next().ok_or("open needs a session file")?;
+                    parsed.open = Some(PathBuf::from(path));
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        if parsed.last_region && parsed.region.is_some() {
+            return Err("--last-region and --region can't be combined".to_string());
+        }
+        if parsed.hints && parsed.is_non_interactive() {
+            return Err("--hints can't be combined with --last-region or --region".to_string());
+        }
+        if parsed.open.is_some() && parsed.is_non_interactive() {
+            return Err("open can't be combined with --last-region or --region".to_string());
+        }
+        if parsed.save && !parsed.is_non_interactive() {
+            return Err("--save can only be used with --last-region or --region".to_string());
+        }
+        if !parsed.boxes.is_empty() && !parsed.save {
+            return Err("--box can only be used with --save".to_string());
+        }
+        let styled = parsed.format.is_some() || parsed.caption.is_some() || parsed.border.is_some();
+        if styled && parsed.is_non_interactive() && !parsed.save {
+            return Err(
+                "--format, --caption and --border need --save with --last-region or --region"
+                    .to_string(),
+            );
+        }
+
+        Ok(parsed)
+    }
+
+    /// Whether a region was requested that is captured without the overlay
+    pub fn is_non_interactive(&self) -> bool {
+        self.last_region || self.region.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = parse(&["--last-region", "--save"]).unwrap();
+        assert!(args.last_region && args.save && args.is_non_interactive());
+
+        let args = parse(&["--region", "code", "--save"]).unwrap();
+        assert_eq!(args.region.as_deref(), Some("code"));
+        assert!(args.save && args.is_non_interactive());
+
+        let args = parse(&["--hints"]).unwrap();
+        assert!(args.hints && !args.is_non_interactive());
+
+        let args = parse(&["open", "capture.waysnip", "--hints"]).unwrap();
+        assert_eq!(args.open, Some(PathBuf::from("capture.waysnip")));
+        assert!(args.hints && !args.is_non_interactive());
+
+        let args = parse(&[
+            "--region",
+            "code",
+            "--save",
+            "--format",
+            "svg",
+            "--box",
+            "10,20 100x50",
+            "--box",
+            "0,0 5x5",
+            "--caption",
+            "Before",
+            "--border",
+            "4",
+        ])
+        .unwrap();
+        assert_eq!(args.format, Some(ExportFormat::Svg));
+        assert_eq!(
+            args.boxes,
+            [
+                Rect::new(10.0, 20.0, 100.0, 50.0),
+                Rect::new(0.0, 0.0, 5.0, 5.0)
+            ]
+        );
+        assert_eq!(args.caption.as_deref(), Some("Before"));
+        assert_eq!(args.border, Some(4.0));
+
+        // Styling applies to interactive exports too
+        assert!(parse(&["--format", "pdf", "--border", "0"]).is_ok());
+
+        assert!(parse(&["-h"]).unwrap().help);
+        assert!(parse(&["--help"]).unwrap().help);
+        assert!(!parse(&[]).unwrap().is_non_interactive());
+    }
+
+    #[test]
+    fn missing_values() {
+        for option in [
+            "--region",
+            "--format",
+            "--box",
+            "--caption",
+            "--border",
+            "open",
+        ] {
+            let error = parse(&[option]).unwrap_err();
+            assert!(error.starts_with(option), "{}", error);
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            parse(&["--frobnicate"]).unwrap_err(),
+            "Unknown argument: --frobnicate"
+        );
+        assert_eq!(
+            parse(&["--format", "gif"]).unwrap_err(),
+            "Unknown format: gif"
+        );
+        assert_eq!(
+            parse(&["--box", "wide"]).unwrap_err(),
+            "Invalid box \"wide\", expected \"X,Y WxH\""
+        );
+        for width in ["-1", "thick", "1000"] {
+            assert_eq!(
+                parse(&["--border", width]).unwrap_err(),
+                format!("Border must be 0-{} pixels", MAX_BORDER)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_combinations() {
+        let cases: &[(&[&str], &str)] = &[
+            (
+                &["--last-region", "--region", "code"],
+                "--last-region and --region can't be combined",
+            ),
+            (
+                &["--last-region", "--hints"],
+                "--hints can't be combined with --last-region or --region",
+            ),
+            (
+                &["--region", "code", "--hints"],
+                "--hints can't be combined with --last-region or --region",
+            ),
+            (
+                &["open", "capture.waysnip", "--last-region"],
+                "open can't be combined with --last-region or --region",
+            ),
+            (
+                &["--save"],
+                "--save can only be used with --last-region or --region",
+            ),
+            (&["--box", "0,0 5x5"], "--box can only be used with --save"),
+            (
+                &["--last-region", "--box", "0,0 5x5"],
+                "--box can only be used with --save",
+            ),
+        ];
+        for (args, error) in cases {
+            assert_eq!(parse(args).unwrap_err(), *error, "{:?}", args);
+        }
+
+        let styled = "--format, --caption and --border need --save with --last-region or --region";
+        for option in [
+            ["--format", "svg"],
+            ["--caption", "Before"],
+            ["--border", "2"],
+        ] {
+            let args = [&["--last-region"], &option[..]].concat();
+            assert_eq!(parse(&args).unwrap_err(), styled);
+        }
+    }
+}
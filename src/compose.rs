@@ -0,0 +1,66 @@
+//! Layout of several selected areas exported together
+
+/// Space between areas placed side by side
+pub const PACKED_GAP: i32 = 16;
+
+/// How several selected areas are exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiExport {
+    /// One image with the areas at their relative positions on screen
+    #[default]
+    Canvas,
+    /// One image with the areas side by side, left to right
+    Packed,
+    /// One file per area
+    Separate,
+}
+
+impl MultiExport {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "canvas" => Some(MultiExport::Canvas),
+            "packed" => Some(MultiExport::Packed),
+            "separate" => Some(MultiExport::Separate),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a "#rrggbb" color
+pub fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Place the areas in one image. Returns the image size and the position
+/// of each area within it.
+pub fn layout(regions: &[(i32, i32, i32, i32)], packed: bool) -> ((i32, i32), Vec<(i32, i32)>) {
+    if packed {
+        let mut x = 0;
+        let positions = regions
+            .iter()
+            .map(|&(_, _, w, _)| {
+                let position = (x, 0);
+                x += w + PACKED_GAP;
+                position
+            })
+            .collect();
+        let width = (x - PACKED_GAP).max(0);
+        let height = regions.iter().map(|r| r.3).max().unwrap_or(0);
+        return ((width, height), positions);
+    }
+
+    let min_x = regions.iter().map(|r| r.0).min().unwrap_or(0);
+    let min_y = regions.iter().map(|r| r.1).min().unwrap_or(0);
+    let max_x = regions.iter().map(|r| r.0 + r.2).max().unwrap_or(0);
+    let max_y = regions.iter().map(|r| r.1 + r.3).max().unwrap_or(0);
+    let positions = regions
+        .iter()
+        .map(|&(x, y, _, _)| (x - min_x, y - min_y))
+        .collect();
+    ((max_x - min_x, max_y - min_y), positions)
+}
@@ -0,0 +1,497 @@
+//! User configuration loaded from $XDG_CONFIG_HOME/waysnip/config.toml
+
+use crate::annotate::{self, StepStyle};
+use crate::beautify::{self, Background, Preset, Style};
+use crate::compose::{self, MultiExport};
+use crate::hints::HintAction;
+use crate::regions::{NamedRegion, RegionSpec};
+use crate::selection::SizePreset;
+use crate::shadow::ShadowCrop;
+use crate::trim;
+use crate::wand::DEFAULT_TOLERANCE;
+use std::path::PathBuf;
+
+/// Size presets offered when the config doesn't define any
+const DEFAULT_SIZE_PRESETS: &[&str] = &["16:9", "4:3", "1:1", "1280x720", "1200x630"];
+
+/// Error type for config operations
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadError(String),
+    ParseError(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::ReadError(msg) => write!(f, "Failed to read config: {}", msg),
+            ConfigError::ParseError(msg) => write!(f, "Invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// User configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Selection size presets, chosen with number keys or the toolbar
+    pub size_presets: Vec<SizePreset>,
+    /// Snap selection edges to strong edges in the screenshot, predefined
+    /// regions and output borders
+    pub snap_to_edges: bool,
+    /// Start with the last exported region already selected
+    pub restore_last_region: bool,
+    /// Offer windows and outputs from the compositor as predefined regions
+    /// when none are piped in
+    pub window_regions: bool,
+    /// Detect windows, panels and buttons in the screenshot as predefined
+    /// regions when the compositor can't be asked
+    pub detect_regions: bool,
+    /// What typing a hint does after selecting its region
+    pub hint_action: HintAction,
+    /// Maximum per-channel color difference for the magic wand (0-255)
+    pub wand_tolerance: u8,
+    /// Maximum per-channel color difference for trimming uniform borders
+    pub trim_tolerance: u8,
+    /// How windows picked from the compositor's regions are cropped, and
+    /// the same per app id or class
+    pub window_shadow: ShadowCrop,
+    pub app_window_shadows: Vec<(String, ShadowCrop)>,
+    /// Colors offered for annotations, the first one used initially
+    pub annotation_colors: Vec<[u8; 3]>,
+    /// Initial stroke width of annotations
+    pub annotation_width: f32,
+    /// Initial font family, size in pixels and background box of text
+    pub text_font: String,
+    pub text_size: f32,
+    pub text_background: bool,
+    /// Radius in pixels and look of step markers
+    pub step_size: f32,
+    pub step_style: StepStyle,
+    /// How several areas selected with Ctrl+drag are exported
+    pub multi_export: MultiExport,
+    /// Background behind several areas exported as one image, transparent
+    /// if unset
+    pub multi_background: Option<[u8; 3]>,
+    /// Named regions, used with --region or the quick-pick menu
+    pub regions: Vec<NamedRegion>,
+    /// Whether exports start beautified, the presets offered and the one
+    /// used initially
+    pub beautify: bool,
+    pub beautify_presets: Vec<Preset>,
+    pub beautify_preset: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            size_presets: DEFAULT_SIZE_PRESETS
+                .iter()
+                .filter_map(|s| SizePreset::parse(s))
+                .collect(),
+            snap_to_edges: false,
+            restore_last_region: false,
+            window_regions: true,
+            detect_regions: true,
+            hint_action: HintAction::default(),
+            wand_tolerance: DEFAULT_TOLERANCE,
+            trim_tolerance: trim::DEFAULT_TOLERANCE,
+            window_shadow: ShadowCrop::default(),
+            app_window_shadows: Vec::new(),
+            annotation_colors: annotate::DEFAULT_COLORS.to_vec(),
+            annotation_width: annotate::DEFAULT_WIDTH,
+            text_font: annotate::DEFAULT_FONT.to_string(),
+            text_size: annotate::DEFAULT_FONT_SIZE,
+            text_background: false,
+            step_size: annotate::DEFAULT_STEP_RADIUS,
+            step_style: StepStyle::default(),
+            multi_export: MultiExport::default(),
+            multi_background: None,
+            regions: Vec::new(),
+            beautify: false,
+            beautify_presets: beautify::default_presets(),
+            beautify_preset: 0,
+        }
+    }
+}
+
+impl Config {
+    /// Get the config file path
+    /// Uses $XDG_CONFIG_HOME/waysnip/config.toml, falling back to ~/.config
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+        };
+        Some(config_dir.join("waysnip").join("config.toml"))
+    }
+
+    /// Load the config file, using defaults for anything it doesn't set.
+    /// Errors are reported on stderr and the defaults are used instead.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match std::fs::read_to_string(&path)
+            .map_err(|e| ConfigError::ReadError(e.to_string()))
+            .and_then(|contents| Self::parse(&contents))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse config file contents
+    ///
+    /// ```toml
+    /// presets = ["16:9", "4:3", "1280x720"]
+    /// snap_to_edges = true
+    /// restore_last_region = false
+    /// window_regions = true
+    /// detect_regions = true
+    /// hint_action = "select"
+    /// wand_tolerance = 32
+    /// trim_tolerance = 16
+    /// window_shadow = "keep"
+    /// annotation_colors = ["#e01b24", "#f6d32d", "#3584e4"]
+    /// annotation_width = 4
+    /// text_font = "Sans"
+    /// text_size = 24
+    /// text_background = false
+    /// step_size = 14
+    /// step_style = "filled"
+    /// multi_export = "canvas"
+    /// multi_background = "transparent"
+    /// beautify = false
+    /// beautify_preset = "slides"
+    ///
+    /// [regions]
+    /// dashboard = "0,40 1920x1000"
+    /// middle = "center 50% x 50%"
+    /// left = "output DP-1"
+    ///
+    /// [window_shadows]
+    /// firefox = "tight"
+    /// "org.gnome.Nautilus" = "shadow"
+    ///
+    /// [beautify_presets.slides]
+    /// padding = 48
+    /// background = "#1c71d8 #33d17a"
+    /// shadow = 24
+    /// radius = 10
+    /// ```
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::ParseError(e.to_string()))?;
+
+        let mut config = Self::default();
+
+        if let Some(value) = table.get("presets") {
+            let presets = value
+                .as_array()
+                .ok_or_else(|| ConfigError::ParseError("presets must be a list".to_string()))?;
+            config.size_presets = presets
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .and_then(SizePreset::parse)
+                        .ok_or_else(|| ConfigError::ParseError(format!("invalid preset {}", v)))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(value) = table.get("snap_to_edges") {
+            config.snap_to_edges = parse_bool("snap_to_edges", value)?;
+        }
+
+        if let Some(value) = table.get("restore_last_region") {
+            config.restore_last_region = parse_bool("restore_last_region", value)?;
+        }
+
+        if let Some(value) = table.get("window_regions") {
+            config.window_regions = parse_bool("window_regions", value)?;
+        }
+
+        if let Some(value) = table.get("detect_regions") {
+            config.detect_regions = parse_bool("detect_regions", value)?;
+        }
+
+        if let Some(value) = table.get("hint_action") {
+            config.hint_action = value.as_str().and_then(HintAction::parse).ok_or_else(|| {
+                ConfigError::ParseError(
+                    "hint_action must be \"select\", \"copy\" or \"save\"".to_string(),
+                )
+            })?;
+        }
+
+        if let Some(value) = table.get("wand_tolerance") {
+            config.wand_tolerance = value
+                .as_integer()
+                .and_then(|v| u8::try_from(v).ok())
+                .ok_or_else(|| {
+                    ConfigError::ParseError("wand_tolerance must be between 0 and 255".to_string())
+                })?;
+        }
+
+        if let Some(value) = table.get("trim_tolerance") {
+            config.trim_tolerance = value
+                .as_integer()
+                .and_then(|v| u8::try_from(v).ok())
+                .ok_or_else(|| {
+                    ConfigError::ParseError("trim_tolerance must be between 0 and 255".to_string())
+                })?;
+        }
+
+        if let Some(value) = table.get("window_shadow") {
+            config.window_shadow = parse_shadow_crop("window_shadow", value)?;
+        }
+
+        if let Some(value) = table.get("window_shadows") {
+            let apps = value.as_table().ok_or_else(|| {
+                ConfigError::ParseError("window_shadows must be a table".to_string())
+            })?;
+            config.app_window_shadows = apps
+                .iter()
+                .map(|(app, v)| {
+                    let key = format!("window_shadows.{}", app);
+                    Ok((app.clone(), parse_shadow_crop(&key, v)?))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(value) = table.get("annotation_colors") {
+            let colors = value.as_array().ok_or_else(|| {
+                ConfigError::ParseError("annotation_colors must be a list".to_string())
+            })?;
+            config.annotation_colors = colors
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .and_then(compose::parse_color)
+                        .ok_or_else(|| ConfigError::ParseError(format!("invalid color {}", v)))
+                })
+                .collect::<Result<_, _>>()?;
+            if config.annotation_colors.is_empty() {
+                return Err(ConfigError::ParseError(
+                    "annotation_colors must not be empty".to_string(),
+                ));
+            }
+        }
+
+        if let Some(value) = table.get("annotation_width") {
+            config.annotation_width = value
+                .as_float()
+                .or_else(|| value.as_integer().map(|v| v as f64))
+                .map(|v| v as f32)
+                .filter(|v| (annotate::MIN_WIDTH..=annotate::MAX_WIDTH).contains(v))
+                .ok_or_else(|| {
+                    ConfigError::ParseError(format!(
+                        "annotation_width must be between {} and {}",
+                        annotate::MIN_WIDTH,
+                        annotate::MAX_WIDTH
+                    ))
+                })?;
+        }
+
+        if let Some(value) = table.get("text_font") {
+            config.text_font = value
+                .as_str()
+                .map(str::trim)
+                .filter(|font| !font.is_empty())
+                .ok_or_else(|| {
+                    ConfigError::ParseError("text_font must be a font family name".to_string())
+                })?
+                .to_string();
+        }
+
+        if let Some(value) = table.get("text_size") {
+            config.text_size = value
+                .as_float()
+                .or_else(|| value.as_integer().map(|v| v as f64))
+                .map(|v| v as f32)
+                .filter(|v| (annotate::MIN_FONT_SIZE..=annotate::MAX_FONT_SIZE).contains(v))
+                .ok_or_else(|| {
+                    ConfigError::ParseError(format!(
+                        "text_size must be between {} and {}",
+                        annotate::MIN_FONT_SIZE,
+                        annotate::MAX_FONT_SIZE
+                    ))
+                })?;
+        }
+
+        if let Some(value) = table.get("text_background") {
+            config.text_background = parse_bool("text_background", value)?;
+        }
+
+        if let Some(value) = table.get("step_size") {
+            config.step_size = value
+                .as_float()
+                .or_else(|| value.as_integer().map(|v| v as f64))
+                .map(|v| v as f32)
+                .filter(|v| (annotate::MIN_STEP_RADIUS..=annotate::MAX_STEP_RADIUS).contains(v))
+                .ok_or_else(|| {
+                    ConfigError::ParseError(format!(
+                        "step_size must be between {} and {}",
+                        annotate::MIN_STEP_RADIUS,
+                        annotate::MAX_STEP_RADIUS
+                    ))
+                })?;
+        }
+
+        if let Some(value) = table.get("step_style") {
+            config.step_style = value.as_str().and_then(StepStyle::parse).ok_or_else(|| {
+                ConfigError::ParseError("step_style must be \"filled\" or \"outline\"".to_string())
+            })?;
+        }
+
+        if let Some(value) = table.get("multi_export") {
+            config.multi_export = value.as_str().and_then(MultiExport::parse).ok_or_else(|| {
+                ConfigError::ParseError(
+                    "multi_export must be \"canvas\", \"packed\" or \"separate\"".to_string(),
+                )
+            })?;
+        }
+
+        if let Some(value) = table.get("multi_background") {
+            let color = value.as_str().unwrap_or_default();
+            config.multi_background = match color {
+                "transparent" => None,
+                _ => Some(compose::parse_color(color).ok_or_else(|| {
+                    ConfigError::ParseError(
+                        "multi_background must be \"transparent\" or \"#rrggbb\"".to_string(),
+                    )
+                })?),
+            };
+        }
+
+        if let Some(value) = table.get("regions") {
+            let regions = value
+                .as_table()
+                .ok_or_else(|| ConfigError::ParseError("regions must be a table".to_string()))?;
+            config.regions = regions
+                .iter()
+                .map(|(name, v)| {
+                    let spec = v.as_str().and_then(RegionSpec::parse).ok_or_else(|| {
+                        ConfigError::ParseError(format!("invalid region {} = {}", name, v))
+                    })?;
+                    Ok(NamedRegion {
+                        name: name.clone(),
+                        spec,
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(value) = table.get("beautify") {
+            config.beautify = parse_bool("beautify", value)?;
+        }
+
+        if let Some(value) = table.get("beautify_presets") {
+            let presets = value.as_table().ok_or_else(|| {
+                ConfigError::ParseError("beautify_presets must be a table".to_string())
+            })?;
+            config.beautify_presets = presets
+                .iter()
+                .map(|(name, v)| parse_beautify_preset(name, v))
+                .collect::<Result<_, _>>()?;
+            if config.beautify_presets.is_empty() {
+                return Err(ConfigError::ParseError(
+                    "beautify_presets must not be empty".to_string(),
+                ));
+            }
+        }
+
+        if let Some(value) = table.get("beautify_preset") {
+            let name = value.as_str().unwrap_or_default();
+            config.beautify_preset = config
+                .beautify_presets
+                .iter()
+                .position(|p| p.name == name)
+                .ok_or_else(|| {
+                    ConfigError::ParseError(format!("No beautify preset named {}", value))
+                })?;
+        }
+
+        Ok(config)
+    }
+
+    /// The style exports start beautified with, if `beautify` is on
+    pub fn beautify_style(&self) -> Option<Style> {
+        let preset = self.beautify_presets.get(self.beautify_preset);
+        preset.filter(|_| self.beautify).map(|p| p.style)
+    }
+
+    /// Find a named region by name
+    pub fn region(&self, name: &str) -> Option<&NamedRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+}
+
+/// Parse a `[beautify_presets.NAME]` table, using the defaults for anything
+/// it doesn't set
+fn parse_beautify_preset(name: &str, value: &toml::Value) -> Result<Preset, ConfigError> {
+    let table = value.as_table().ok_or_else(|| {
+        ConfigError::ParseError(format!("beautify_presets.{} must be a table", name))
+    })?;
+    let number = |key: &str, max: f32| {
+        table
+            .get(key)
+            .map(|v| {
+                v.as_float()
+                    .or_else(|| v.as_integer().map(|v| v as f64))
+                    .map(|v| v as f32)
+                    .filter(|v| (0.0..=max).contains(v))
+                    .ok_or_else(|| {
+                        ConfigError::ParseError(format!(
+                            "beautify_presets.{}.{} must be between 0 and {}",
+                            name, key, max
+                        ))
+                    })
+            })
+            .transpose()
+    };
+
+    let mut style = Style::default();
+    if let Some(padding) = number("padding", beautify::MAX_PADDING)? {
+        style.padding = padding;
+    }
+    if let Some(shadow) = number("shadow", beautify::MAX_SHADOW)? {
+        style.shadow = shadow;
+    }
+    if let Some(radius) = number("radius", beautify::MAX_RADIUS)? {
+        style.radius = radius;
+    }
+    if let Some(value) = table.get("background") {
+        style.background = value.as_str().and_then(Background::parse).ok_or_else(|| {
+            ConfigError::ParseError(format!(
+                "beautify_presets.{}.background must be \"#rrggbb\" or two colors for a gradient",
+                name
+            ))
+        })?;
+    }
+    Ok(Preset {
+        name: name.to_string(),
+        style,
+    })
+}
+
+fn parse_shadow_crop(key: &str, value: &toml::Value) -> Result<ShadowCrop, ConfigError> {
+    value.as_str().and_then(ShadowCrop::parse).ok_or_else(|| {
+        ConfigError::ParseError(format!("{} must be \"keep\", \"tight\" or \"shadow\"", key))
+    })
+}
+
+fn parse_bool(key: &str, value: &toml::Value) -> Result<bool, ConfigError> {
+    value
+        .as_bool()
+        .ok_or_else(|| ConfigError::ParseError(format!("{} must be true or false", key)))
+}
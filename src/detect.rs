@@ -0,0 +1,155 @@
+//! Content-aware region proposals found in the screenshot itself
+//!
+//! Used when the compositor can't be asked for its windows. Outlines of
+//! rectangular UI elements (windows, panels, cards, buttons) are found as
+//! connected edges whose bounding box is almost entirely edge.
+
+use crate::pixels::Pixels;
+use crate::selection::Rect;
+
+/// Minimum luminance step between neighbouring pixels to count as an edge
+const EDGE_CONTRAST: u8 = 24;
+
+/// Smallest width or height of a proposed region
+const MIN_SIDE: i32 = 12;
+
+/// Fraction of the bounding box outline that must be edge
+const MIN_OUTLINE_COVERAGE: f32 = 0.85;
+
+/// Maximum number of regions proposed, largest outlines first
+const MAX_REGIONS: usize = 500;
+
+/// Find rectangular UI elements in the screenshot
+pub fn detect_regions(pixels: &Pixels) -> Vec<Rect> {
+    let (width, height) = (pixels.width, pixels.height);
+    if width < 2 || height < 2 {
+        return Vec::new();
+    }
+    let index = |x: i32, y: i32| (y * width + x) as usize;
+
+    // A pixel is an edge if it differs from its right or lower neighbour, so
+    // an element's outline runs just outside its top and left sides and just
+    // inside its right and bottom sides
+    let luma = pixels.luma_plane();
+    let mut edge = vec![false; luma.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let here = luma[index(x, y)];
+            let right = x + 1 < width && here.abs_diff(luma[index(x + 1, y)]) >= EDGE_CONTRAST;
+            let below = y + 1 < height && here.abs_diff(luma[index(x, y + 1)]) >= EDGE_CONTRAST;
+            edge[index(x, y)] = right || below;
+        }
+    }
+
+    // Label 8-connected edge components and keep those tracing a rectangle
+    let mut label = vec![0u32; luma.len()];
+    let mut next_label = 0;
+    let mut found: Vec<Rect> = Vec::new();
+    let mut stack = Vec::new();
+    for start_y in 0..height {
+        for start_x in 0..width {
+            if !edge[index(start_x, start_y)] || label[index(start_x, start_y)] != 0 {
+                continue;
+            }
+            next_label += 1;
+            label[index(start_x, start_y)] = next_label;
+            stack.push((start_x, start_y));
+            let (mut x0, mut y0, mut x1, mut y1) = (start_x, start_y, start_x, start_y);
+
+            while let Some((x, y)) = stack.pop() {
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x);
+                y1 = y1.max(y);
+                for ny in (y - 1).max(0)..=(y + 1).min(height - 1) {
+                    for nx in (x - 1).max(0)..=(x + 1).min(width - 1) {
+                        if edge[index(nx, ny)] && label[index(nx, ny)] == 0 {
+                            label[index(nx, ny)] = next_label;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            // The element spans from just inside the top-left of the outline
+            // to its bottom-right
+            let rect = (x0 + 1, y0 + 1, x1 - x0, y1 - y0);
+            if rect.2 < MIN_SIDE || rect.3 < MIN_SIDE {
+                continue;
+            }
+            let on_outline = |x: i32, y: i32| label[index(x, y)] == next_label;
+            let covered = (x0..=x1)
+                .filter(|&x| on_outline(x, y0))
+                .chain((x0..=x1).filter(|&x| on_outline(x, y1)))
+                .count()
+                + (y0..=y1)
+                    .filter(|&y| on_outline(x0, y))
+                    .chain((y0..=y1).filter(|&y| on_outline(x1, y)))
+                    .count();
+            let outline_length = 2 * (x1 - x0 + 1) + 2 * (y1 - y0 + 1);
+            if (covered as f32) < outline_length as f32 * MIN_OUTLINE_COVERAGE {
+                continue;
+            }
+
+            let rect = Rect::new(rect.0 as f32, rect.1 as f32, rect.2 as f32, rect.3 as f32);
+            if !found.contains(&rect) {
+                found.push(rect);
+            }
+        }
+    }
+
+    found.sort_by(|a, b| (b.width * b.height).total_cmp(&(a.width * a.height)));
+    found.truncate(MAX_REGIONS);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    /// An 80x60 white screenshot with filled gray boxes, later ones on top
+    fn screenshot(boxes: &[(i32, i32, i32, i32, u8)]) -> Pixels {
+        Pixels::from_fn(80, 60, |x, y| {
+            let top = boxes.iter().rev().find(|&&(bx, by, bw, bh, _)| {
+                (bx..bx + bw).contains(&x) && (by..by + bh).contains(&y)
+            });
+            [top.map_or(255, |b| b.4); 3]
+        })
+    }
+
+    #[test]
+    fn finds_boxes_largest_first() {
+        let pixels = screenshot(&[(10, 10, 50, 40, 200), (20, 20, 20, 14, 80)]);
+        assert_eq!(
+            detect_regions(&pixels),
+            vec![
+                Rect::new(10.0, 10.0, 50.0, 40.0),
+                Rect::new(20.0, 20.0, 20.0, 14.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_small_and_faint_elements() {
+        let pixels = screenshot(&[(10, 10, 8, 30, 0), (30, 10, 30, 30, 240)]);
+        assert_eq!(detect_regions(&pixels), Vec::new());
+    }
+
+    #[test]
+    fn ignores_shapes_that_arent_rectangles() {
+        // A diagonal line has a large bounding box but little outline
+        let pixels = Pixels::from_fn(80, 60, |x, y| if x == y { [0, 0, 0] } else { WHITE });
+        assert_eq!(detect_regions(&pixels), Vec::new());
+    }
+
+    #[test]
+    fn empty_screenshot() {
+        assert_eq!(detect_regions(&screenshot(&[])), Vec::new());
+        assert_eq!(
+            detect_regions(&Pixels::from_fn(1, 1, |_, _| WHITE)),
+            Vec::new()
+        );
+    }
+}
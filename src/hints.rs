@@ -0,0 +1,128 @@
+//! Vimium-style letter hints for selecting regions with the keyboard
+
+use crate::selection::Rect;
+
+/// Letters hints are made of, home row first
+const HINT_CHARS: &[u8] = b"asdfghjklqwertyuiop";
+
+/// What happens once a hint has been typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HintAction {
+    /// Select the region and keep the overlay open
+    #[default]
+    Select,
+    /// Select the region and copy it to the clipboard
+    Copy,
+    /// Select the region and save it to a file
+    Save,
+}
+
+impl HintAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "select" => Some(HintAction::Select),
+            "copy" => Some(HintAction::Copy),
+            "save" => Some(HintAction::Save),
+            _ => None,
+        }
+    }
+}
+
+/// Result of typing a letter while hints are shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintInput {
+    /// The letters typed so far start one or more hints
+    Pending,
+    /// A hint was completed
+    Matched(Rect),
+    /// No hint starts with the letters typed, the letter was ignored
+    NoMatch,
+}
+
+/// A region with the hint that selects it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HintTarget {
+    pub hint: String,
+    pub rect: Rect,
+}
+
+/// Hints laid over the regions while hint mode is active
+#[derive(Debug, Clone, Default)]
+pub struct Hints {
+    pub targets: Vec<HintTarget>,
+    /// Letters typed so far
+    pub typed: String,
+}
+
+impl Hints {
+    /// Assign hints to regions, skipping duplicates. Hints are handed out
+    /// top to bottom, left to right.
+    pub fn new(regions: impl IntoIterator<Item = Rect>) -> Self {
+        let mut rects: Vec<Rect> = Vec::new();
+        for rect in regions {
+            let rect = rect.normalized();
+            if !rects.contains(&rect) {
+                rects.push(rect);
+            }
+        }
+        rects.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+        let targets = hint_labels(rects.len())
+            .into_iter()
+            .zip(rects)
+            .map(|(hint, rect)| HintTarget { hint, rect })
+            .collect();
+        Self {
+            targets,
+            typed: String::new(),
+        }
+    }
+
+    /// Type a letter of a hint
+    pub fn type_char(&mut self, c: char) -> HintInput {
+        let mut typed = self.typed.clone();
+        typed.push(c.to_ascii_lowercase());
+
+        if let Some(target) = self.targets.iter().find(|t| t.hint == typed) {
+            return HintInput::Matched(target.rect);
+        }
+        if self.targets.iter().any(|t| t.hint.starts_with(&typed)) {
+            self.typed = typed;
+            HintInput::Pending
+        } else {
+            HintInput::NoMatch
+        }
+    }
+
+    /// Remove the last typed letter
+    pub fn backspace(&mut self) {
+        self.typed.pop();
+    }
+
+    /// Targets whose hint still matches the letters typed so far
+    pub fn visible_targets(&self) -> impl Iterator<Item = &HintTarget> {
+        self.targets
+            .iter()
+            .filter(|t| t.hint.starts_with(&self.typed))
+    }
+}
+
+/// Generate `count` hints of equal length, so no hint is a prefix of another
+fn hint_labels(count: usize) -> Vec<String> {
+    let base = HINT_CHARS.len();
+    let mut length = 1;
+    while base.pow(length) < count {
+        length += 1;
+    }
+
+    (0..count)
+        .map(|mut n| {
+            let mut hint = vec![0u8; length as usize];
+            for slot in hint.iter_mut().rev() {
+                *slot = HINT_CHARS[n % base];
+                n /= base;
+            }
+            String::from_utf8(hint).unwrap_or_default()
+        })
+        .collect()
+}
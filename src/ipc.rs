@@ -0,0 +1,602 @@
+//! Predefined regions queried from the compositor over its IPC socket
+//!
+//! Sway reports outputs and windows through `get_tree`. Hyprland reports
+//! windows (`clients`), outputs (`monitors`) and layer surfaces (`layers`).
+
+use crate::selection::{PredefinedRegion, Rect};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait for the compositor before giving up
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// i3-ipc message type of `get_tree`
+const SWAY_GET_TREE: u32 = 4;
+
+/// Magic string that starts every i3-ipc message
+const SWAY_MAGIC: &[u8] = b"i3-ipc";
+
+/// Error type for IPC operations
+#[derive(Debug)]
+pub enum IpcError {
+    ConnectError(String),
+    IoError(String),
+    InvalidReply(String),
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpcError::ConnectError(msg) => write!(f, "Failed to connect to compositor: {}", msg),
+            IpcError::IoError(msg) => write!(f, "Compositor IPC failed: {}", msg),
+            IpcError::InvalidReply(msg) => write!(f, "Unexpected compositor reply: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+/// A compositor and the socket its regions are queried from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compositor {
+    Sway(PathBuf),
+    Hyprland(PathBuf),
+}
+
+impl Compositor {
+    /// Detect the running compositor from the environment
+    pub fn detect() -> Option<Self> {
+        if let Some(path) = std::env::var_os("SWAYSOCK").filter(|p| !p.is_empty()) {
+            return Some(Compositor::Sway(PathBuf::from(path)));
+        }
+
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_default();
+        let path = Path::new(&runtime_dir)
+            .join("hypr")
+            .join(&signature)
+            .join(".socket.sock");
+        // Older Hyprland versions keep their sockets in /tmp
+        let legacy = Path::new("/tmp/hypr").join(&signature).join(".socket.sock");
+        if !path.exists() && legacy.exists() {
+            return Some(Compositor::Hyprland(legacy));
+        }
+        Some(Compositor::Hyprland(path))
+    }
+
+    /// Query visible windows, layer surfaces and outputs as labelled regions,
+    /// in screenshot coordinates (the top-left-most output starts at 0,0).
+    /// Windows come first, then layer surfaces, then outputs.
+    pub fn regions(&self) -> Result<Vec<PredefinedRegion>, IpcError> {
+        let layout = match self {
+            Compositor::Sway(socket) => sway_layout(socket)?,
+            Compositor::Hyprland(socket) => hyprland_layout(socket)?,
+        };
+        Ok(layout.into_regions())
+    }
+}
+
+/// Regions reported by the compositor, in global layout coordinates
+#[derive(Default)]
+struct Layout {
+    windows: Vec<PredefinedRegion>,
+    layers: Vec<PredefinedRegion>,
+    outputs: Vec<PredefinedRegion>,
+}
+
+impl Layout {
+    /// Move everything so the top-left-most output is at 0,0, the same way
+    /// the outputs are combined into the screenshot
+    fn into_regions(self) -> Vec<PredefinedRegion> {
+        let min_x = self.outputs.iter().map(|o| o.rect.x).reduce(f32::min);
+        let min_y = self.outputs.iter().map(|o| o.rect.y).reduce(f32::min);
+        let (dx, dy) = (min_x.unwrap_or(0.0), min_y.unwrap_or(0.0));
+
+        self.windows
+            .into_iter()
+            .chain(self.layers)
+            .chain(self.outputs)
+            .filter(|r| r.rect.width > 0.0 && r.rect.height > 0.0)
+            .map(|mut r| {
+                r.rect.x -= dx;
+                r.rect.y -= dy;
+                r
+            })
+            .collect()
+    }
+}
+
+/// Send a single i3-ipc message and return the parsed reply
+fn sway_request(socket: &Path, message_type: u32, payload: &str) -> Result<Value, IpcError> {
+    let mut stream = connect(socket)?;
+
+    let mut message = Vec::with_capacity(SWAY_MAGIC.len() + 8 + payload.len());
+    message.extend_from_slice(SWAY_MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream
+        .write_all(&message)
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+
+    let mut header = [0u8; 14];
+    stream
+        .read_exact(&mut header)
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+    if &header[..6] != SWAY_MAGIC {
+        return Err(IpcError::InvalidReply("missing i3-ipc header".to_string()));
+    }
+    let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+
+    let mut body = vec![0u8; length];
+    stream
+        .read_exact(&mut body)
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+    parse_reply(&body)
+}
+
+/// Collect visible windows and active outputs from Sway's tree
+fn sway_layout(socket: &Path) -> Result<Layout, IpcError> {
+    let tree = sway_request(socket, SWAY_GET_TREE, "")?;
+    let mut layout = Layout::default();
+    collect_sway_nodes(&tree, &mut layout);
+    Ok(layout)
+}
+
+fn collect_sway_nodes(node: &Value, layout: &mut Layout) {
+    let node_type = node.get("type").and_then(Value::as_str).unwrap_or("");
+    let name = node.get("name").and_then(Value::as_str).unwrap_or("");
+
+    match node_type {
+        // The scratchpad lives on the hidden __i3 output
+        "output" if name == "__i3" => return,
+        "output" => {
+            let active = node.get("active").and_then(Value::as_bool).unwrap_or(true);
+            if let (true, Some(rect)) = (active, node.get("rect").and_then(sway_rect)) {
+                layout.outputs.push(PredefinedRegion::labelled(rect, name));
+            }
+        }
+        "con" | "floating_con" if node.get("visible").and_then(Value::as_bool) == Some(true) => {
+            if let Some(rect) = node.get("rect").and_then(sway_rect) {
+                let app = node
+                    .get("app_id")
+                    .and_then(Value::as_str)
+                    .or_else(|| {
+                        node.get("window_properties")
+                            .and_then(|p| p.get("class"))
+                            .and_then(Value::as_str)
+                    })
+                    .unwrap_or("");
+                layout
+                    .windows
+                    .push(PredefinedRegion::labelled(rect, window_label(name, app)).with_app(app));
+            }
+        }
+        _ => {}
+    }
+
+    for key in ["nodes", "floating_nodes"] {
+        for child in node
+            .get(key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            collect_sway_nodes(child, layout);
+        }
+    }
+}
+
+fn sway_rect(rect: &Value) -> Option<Rect> {
+    let field = |key| rect.get(key).and_then(Value::as_f64).map(|v| v as f32);
+    Some(Rect::new(
+        field("x")?,
+        field("y")?,
+        field("width")?,
+        field("height")?,
+    ))
+}
+
+/// Send a single request to Hyprland's command socket and return the
+/// parsed reply. Hyprland closes the connection after replying.
+fn hyprland_request(socket: &Path, command: &str) -> Result<Value, IpcError> {
+    let mut stream = connect(socket)?;
+    stream
+        .write_all(format!("j/{}", command).as_bytes())
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+
+    let mut reply = Vec::new();
+    stream
+        .read_to_end(&mut reply)
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+    parse_reply(&reply)
+}
+
+/// Collect windows on visible workspaces, layer surfaces and outputs from Hyprland
+fn hyprland_layout(socket: &Path) -> Result<Layout, IpcError> {
+    let monitors = hyprland_request(socket, "monitors")?;
+    let clients = hyprland_request(socket, "clients")?;
+    let layers = hyprland_request(socket, "layers")?;
+    let mut layout = Layout::default();
+
+    // Workspaces shown on some monitor, including open special workspaces
+    let mut visible_workspaces = Vec::new();
+    for monitor in monitors.as_array().into_iter().flatten() {
+        for key in ["activeWorkspace", "specialWorkspace"] {
+            let id = monitor
+                .get(key)
+                .and_then(|w| w.get("id"))
+                .and_then(Value::as_f64);
+            if let Some(id) = id.filter(|&id| id != 0.0) {
+                visible_workspaces.push(id);
+            }
+        }
+
+        let number = |key| monitor.get(key).and_then(Value::as_f64);
+        let (Some(x), Some(y), Some(width), Some(height)) =
+            (number("x"), number("y"), number("width"), number("height"))
+        else {
+            continue;
+        };
+        // The reported size is in physical pixels, before rotation
+        let scale = number("scale").filter(|&s| s > 0.0).unwrap_or(1.0);
+        let (width, height) = match number("transform").unwrap_or(0.0) as i32 % 2 {
+            0 => (width / scale, height / scale),
+            _ => (height / scale, width / scale),
+        };
+        let name = monitor.get("name").and_then(Value::as_str).unwrap_or("");
+        layout.outputs.push(PredefinedRegion::labelled(
+            Rect::new(x as f32, y as f32, width as f32, height as f32),
+            name,
+        ));
+    }
+
+    for client in clients.as_array().into_iter().flatten() {
+        let flag = |key| client.get(key).and_then(Value::as_bool);
+        let workspace = client
+            .get("workspace")
+            .and_then(|w| w.get("id"))
+            .and_then(Value::as_f64);
+        let visible = flag("mapped") != Some(false)
+            && flag("hidden") != Some(true)
+            && workspace.is_some_and(|id| visible_workspaces.contains(&id));
+        if !visible {
+            continue;
+        }
+
+        let pair = |key| {
+            let values = client.get(key)?.as_array()?;
+            Some((values.first()?.as_f64()?, values.get(1)?.as_f64()?))
+        };
+        let (Some((x, y)), Some((width, height))) = (pair("at"), pair("size")) else {
+            continue;
+        };
+        let title = client.get("title").and_then(Value::as_str).unwrap_or("");
+        let class = client.get("class").and_then(Value::as_str).unwrap_or("");
+        layout.windows.push(
+            PredefinedRegion::labelled(
+                Rect::new(x as f32, y as f32, width as f32, height as f32),
+                window_label(title, class),
+            )
+            .with_app(class),
+        );
+    }
+
+    // Layers are grouped by monitor, then by level; level 0 holds wallpapers,
+    // which cover the whole output anyway
+    for monitor in layers.as_object().into_iter().flat_map(|m| m.values()) {
+        let levels = monitor.get("levels").and_then(Value::as_object);
+        for (level, surfaces) in levels.into_iter().flatten() {
+            if level == "0" {
+                continue;
+            }
+            for surface in surfaces.as_array().into_iter().flatten() {
+                let number = |key| surface.get(key).and_then(Value::as_f64).map(|v| v as f32);
+                let (Some(x), Some(y), Some(w), Some(h)) =
+                    (number("x"), number("y"), number("w"), number("h"))
+                else {
+                    continue;
+                };
+                let namespace = surface.get("namespace").and_then(Value::as_str);
+                layout.layers.push(PredefinedRegion::labelled(
+                    Rect::new(x, y, w, h),
+                    namespace.unwrap_or("layer"),
+                ));
+            }
+        }
+    }
+
+    Ok(layout)
+}
+
+fn connect(socket: &Path) -> Result<UnixStream, IpcError> {
+    let stream = UnixStream::connect(socket)
+        .map_err(|e| IpcError::ConnectError(format!("{}: {}", socket.display(), e)))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        .map_err(|e| IpcError::IoError(e.to_string()))?;
+    Ok(stream)
+}
+
+fn parse_reply(reply: &[u8]) -> Result<Value, IpcError> {
+    serde_json::from_slice(reply).map_err(|e| IpcError::InvalidReply(e.to_string()))
+}
+
+/// Label a window by its title, falling back to its app id or class
+fn window_label(title: &str, app: &str) -> String {
+    if title.trim().is_empty() {
+        app.to_string()
+    } else {
+        title.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Accept `connections` clients, handing each one's request to `reply`.
+    /// Returns the requests received.
+    fn serve(
+        socket: &Path,
+        connections: usize,
+        reply: impl Fn(&mut UnixStream) -> Vec<u8> + Send + 'static,
+    ) -> JoinHandle<Vec<Vec<u8>>> {
+        let listener = UnixListener::bind(socket).unwrap();
+        thread::spawn(move || {
+            (0..connections)
+                .map(|_| reply(&mut listener.accept().unwrap().0))
+                .collect()
+        })
+    }
+
+    /// Answer a single i3-ipc request with a framed reply
+    fn serve_sway(socket: &Path, tree: &'static str) -> JoinHandle<Vec<Vec<u8>>> {
+        serve(socket, 1, move |stream| {
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+            let mut payload = vec![0u8; length as usize];
+            stream.read_exact(&mut payload).unwrap();
+
+            let mut reply = SWAY_MAGIC.to_vec();
+            reply.extend_from_slice(&(tree.len() as u32).to_ne_bytes());
+            reply.extend_from_slice(&SWAY_GET_TREE.to_ne_bytes());
+            reply.extend_from_slice(tree.as_bytes());
+            stream.write_all(&reply).unwrap();
+
+            let mut request = header.to_vec();
+            request.extend(payload);
+            request
+        })
+    }
+
+    /// Serve Hyprland replies by command, then close the connection
+    fn serve_hyprland(
+        socket: &Path,
+        connections: usize,
+        replies: &'static [(&str, &str)],
+    ) -> JoinHandle<Vec<Vec<u8>>> {
+        serve(socket, connections, move |stream| {
+            let mut request = vec![0u8; 64];
+            let read = stream.read(&mut request).unwrap();
+            request.truncate(read);
+            let command = std::str::from_utf8(&request).unwrap();
+            let (_, reply) = replies
+                .iter()
+                .find(|(c, _)| format!("j/{}", c) == command)
+                .unwrap_or_else(|| panic!("unexpected command {:?}", command));
+            stream.write_all(reply.as_bytes()).unwrap();
+            request
+        })
+    }
+
+    fn region(x: f32, y: f32, width: f32, height: f32, label: &str) -> PredefinedRegion {
+        PredefinedRegion::labelled(Rect::new(x, y, width, height), label)
+    }
+
+    const SWAY_TREE: &str = r#"{
+        "type": "root", "name": "root",
+        "rect": {"x": 0, "y": 0, "width": 4480, "height": 1440},
+        "nodes": [
+            {"type": "output", "name": "__i3",
+             "rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+             "nodes": [{"type": "workspace", "name": "__i3_scratch", "floating_nodes": [
+                 {"type": "floating_con", "name": "Hidden", "visible": true,
+                  "rect": {"x": 10, "y": 10, "width": 100, "height": 100}}
+             ]}]},
+            {"type": "output", "name": "DP-1", "active": true,
+             "rect": {"x": -1920, "y": 0, "width": 1920, "height": 1080},
+             "nodes": [{"type": "workspace", "name": "1", "nodes": [
+                 {"type": "con", "name": "Terminal", "app_id": "foot", "visible": true,
+                  "rect": {"x": -1920, "y": 0, "width": 960, "height": 1080}},
+                 {"type": "con", "name": "Tabbed away", "app_id": "foot", "visible": false,
+                  "rect": {"x": -960, "y": 0, "width": 960, "height": 1080}}
+             ]}]},
+            {"type": "output", "name": "HDMI-A-1", "active": true,
+             "rect": {"x": 0, "y": 0, "width": 2560, "height": 1440},
+             "nodes": [{"type": "workspace", "name": "2", "nodes": [], "floating_nodes": [
+                 {"type": "floating_con", "name": "", "app_id": null, "visible": true,
+                  "window_properties": {"class": "Firefox", "title": ""},
+                  "rect": {"x": 100, "y": 50, "width": 800, "height": 600}}
+             ]}]},
+            {"type": "output", "name": "DP-2", "active": false,
+             "rect": {"x": 0, "y": 0, "width": 0, "height": 0}}
+        ]
+    }"#;
+
+    #[test]
+    fn sway_request_frames_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve_sway(&socket, r#"{"type": "root"}"#);
+
+        let reply = sway_request(&socket, 0, "nop").unwrap();
+        assert_eq!(reply["type"], "root");
+
+        let requests = server.join().unwrap();
+        let request = &requests[0];
+        assert_eq!(&request[..6], b"i3-ipc");
+        assert_eq!(request[6..10], 3u32.to_ne_bytes());
+        assert_eq!(request[10..14], 0u32.to_ne_bytes());
+        assert_eq!(&request[14..], b"nop");
+    }
+
+    #[test]
+    fn sway_request_rejects_bad_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve(&socket, 1, |stream| {
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            stream.write_all(b"not-ipc-at-all").unwrap();
+            header.to_vec()
+        });
+
+        let result = sway_request(&socket, SWAY_GET_TREE, "");
+        assert!(matches!(result, Err(IpcError::InvalidReply(_))));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn sway_regions() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve_sway(&socket, SWAY_TREE);
+
+        let regions = Compositor::Sway(socket).regions().unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0][10..14], SWAY_GET_TREE.to_ne_bytes());
+        assert_eq!(
+            regions,
+            vec![
+                region(0.0, 0.0, 960.0, 1080.0, "Terminal").with_app("foot"),
+                region(2020.0, 50.0, 800.0, 600.0, "Firefox").with_app("Firefox"),
+                region(0.0, 0.0, 1920.0, 1080.0, "DP-1"),
+                region(1920.0, 0.0, 2560.0, 1440.0, "HDMI-A-1"),
+            ]
+        );
+    }
+
+    const HYPRLAND_REPLIES: &[(&str, &str)] = &[
+        (
+            "monitors",
+            r#"[
+                {"name": "DP-1", "x": 0, "y": 0, "width": 3840, "height": 2160,
+                 "scale": 2.0, "transform": 0,
+                 "activeWorkspace": {"id": 1}, "specialWorkspace": {"id": 0}},
+                {"name": "HDMI-A-1", "x": -1080, "y": -200, "width": 1920, "height": 1080,
+                 "scale": 1.0, "transform": 1,
+                 "activeWorkspace": {"id": 2}, "specialWorkspace": {"id": -98}}
+            ]"#,
+        ),
+        (
+            "clients",
+            r#"[
+                {"title": "vim", "class": "kitty", "at": [10, 20], "size": [800, 600],
+                 "mapped": true, "hidden": false, "workspace": {"id": 1}},
+                {"title": "Elsewhere", "class": "kitty", "at": [0, 0], "size": [800, 600],
+                 "mapped": true, "hidden": false, "workspace": {"id": 3}},
+                {"title": "", "class": "scratch", "at": [-1000, 0], "size": [400, 300],
+                 "mapped": true, "hidden": false, "workspace": {"id": -98}},
+                {"title": "Grouped", "class": "kitty", "at": [10, 20], "size": [800, 600],
+                 "mapped": true, "hidden": true, "workspace": {"id": 1}}
+            ]"#,
+        ),
+        (
+            "layers",
+            r#"{
+                "DP-1": {"levels": {
+                    "0": [{"namespace": "wallpaper", "x": 0, "y": 0, "w": 1920, "h": 1080}],
+                    "2": [{"namespace": "waybar", "x": 0, "y": 0, "w": 1920, "h": 30}]
+                }}
+            }"#,
+        ),
+    ];
+
+    #[test]
+    fn hyprland_request_reads_until_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve_hyprland(&socket, 1, HYPRLAND_REPLIES);
+
+        let reply = hyprland_request(&socket, "monitors").unwrap();
+        assert_eq!(reply[1]["name"], "HDMI-A-1");
+        assert_eq!(server.join().unwrap(), vec![b"j/monitors".to_vec()]);
+    }
+
+    #[test]
+    fn hyprland_regions() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve_hyprland(&socket, 3, HYPRLAND_REPLIES);
+
+        let regions = Compositor::Hyprland(socket).regions().unwrap();
+        server.join().unwrap();
+        assert_eq!(
+            regions,
+            vec![
+                region(1090.0, 220.0, 800.0, 600.0, "vim").with_app("kitty"),
+                region(80.0, 200.0, 400.0, 300.0, "scratch").with_app("scratch"),
+                region(1080.0, 200.0, 1920.0, 30.0, "waybar"),
+                // Scaled down to logical pixels
+                region(1080.0, 200.0, 1920.0, 1080.0, "DP-1"),
+                // Rotated
+                region(0.0, 0.0, 1080.0, 1920.0, "HDMI-A-1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_reply() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let server = serve_hyprland(&socket, 1, &[("clients", "[{\"title\": ")]);
+
+        let result = hyprland_request(&socket, "clients");
+        assert!(matches!(result, Err(IpcError::InvalidReply(_))));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn layout_into_regions() {
+        let layout = Layout {
+            windows: vec![
+                region(-500.0, -100.0, 300.0, 200.0, "window"),
+                region(0.0, 0.0, 0.0, 200.0, "empty"),
+            ],
+            layers: vec![region(-500.0, -100.0, 1000.0, 30.0, "bar")],
+            outputs: vec![
+                region(0.0, 0.0, 1920.0, 1080.0, "right"),
+                region(-1000.0, -100.0, 1000.0, 800.0, "left"),
+            ],
+        };
+        assert_eq!(
+            layout.into_regions(),
+            vec![
+                region(500.0, 0.0, 300.0, 200.0, "window"),
+                region(500.0, 0.0, 1000.0, 30.0, "bar"),
+                region(1000.0, 100.0, 1920.0, 1080.0, "right"),
+                region(0.0, 0.0, 1000.0, 800.0, "left"),
+            ]
+        );
+
+        // Without outputs nothing moves
+        let layout = Layout {
+            windows: vec![region(-5.0, 5.0, 10.0, 10.0, "window")],
+            ..Layout::default()
+        };
+        assert_eq!(
+            layout.into_regions(),
+            vec![region(-5.0, 5.0, 10.0, 10.0, "window")]
+        );
+    }
+}
@@ -1,21 +1,50 @@
 //! Waysnip - A Wayland screenshot selection tool
 
+mod annotate;
+mod beautify;
 mod canvas;
+mod cli;
 mod clipboard;
+mod compose;
+mod config;
+mod detect;
+mod hints;
+mod ipc;
+mod mask;
+mod pixels;
+mod redact;
+mod regions;
 mod screenshot;
 mod selection;
+mod session;
+mod shadow;
+mod snap;
+mod state;
+mod trim;
+mod vector;
+mod wand;
 mod window;
 
+use annotate::{Annotation, AnnotationTool};
+use beautify::Style;
 use canvas::Canvas;
+use compose::MultiExport;
+use config::Config;
+use gdk_pixbuf::Pixbuf;
 use gtk4::gdk;
-use gtk4::gio::ApplicationFlags;
+use gtk4::gio::{self, ApplicationFlags};
 use gtk4::glib;
 use gtk4::prelude::*;
+use hints::{HintAction, HintInput};
+use regions::RegionSpec;
 use screenshot::Screenshot;
-use selection::read_predefined_regions_from_stdin;
+use selection::{read_predefined_regions_from_stdin, PredefinedRegion, SelectionShape, SizePreset};
+use session::Session;
+use state::LastRegion;
 use std::cell::RefCell;
 use std::path::PathBuf;
 use std::rc::Rc;
+use vector::{Callouts, ExportFormat};
 
 const APP_ID: &str = "com.waysnip.Waysnip";
 
@@ -23,9 +52,9 @@ const APP_ID: &str = "com.waysnip.Waysnip";
 type ScreenshotResult<T> = Result<T, String>;
 
 /// Generate a unique screenshot path in $HOME/Pictures
-/// Format: screenshot-YYYY-MM-DD-HH-MM-SS.png
+/// Format: screenshot-YYYY-MM-DD-HH-MM-SS.<extension>
 /// Adds -1, -2, etc. if file exists
-fn generate_screenshot_path() -> Option<PathBuf> {
+fn generate_screenshot_path(extension: &str) -> Option<PathBuf> {
     let home = std::env::var("HOME").ok()?;
     let pictures_dir = PathBuf::from(home).join("Pictures");
 
@@ -39,14 +68,14 @@ fn generate_screenshot_path() -> Option<PathBuf> {
     let base_name = now.format("screenshot-%Y-%m-%d-%H-%M-%S").to_string();
 
     // Try the base name first
-    let mut path = pictures_dir.join(format!("{}.png", base_name));
+    let mut path = pictures_dir.join(format!("{}.{}", base_name, extension));
     if !path.exists() {
         return Some(path);
     }
 
     // If exists, add incrementing number
     for i in 1..1000 {
-        path = pictures_dir.join(format!("{}-{}.png", base_name, i));
+        path = pictures_dir.join(format!("{}-{}.{}", base_name, i, extension));
         if !path.exists() {
             return Some(path);
         }
@@ -54,42 +83,357 @@ fn generate_screenshot_path() -> Option<PathBuf> {
 
     // Fallback with milliseconds if somehow all are taken
     let name_with_ms = now.format("screenshot-%Y-%m-%d-%H-%M-%S-%3f").to_string();
-    Some(pictures_dir.join(format!("{}.png", name_with_ms)))
+    Some(pictures_dir.join(format!("{}.{}", name_with_ms, extension)))
 }
 
-/// Crop and get PNG data from canvas selection
+/// Crop a region of the screenshot, beautified with `style` if set, and get
+/// PNG data
+fn crop_region_png(
+    screenshot: &Screenshot,
+    (x, y, w, h): (i32, i32, i32, i32),
+    style: Option<&Style>,
+) -> ScreenshotResult<Vec<u8>> {
+    let area = beautify_image(style, screenshot.crop_pixbuf(x, y, w, h, None))?;
+    screenshot::encode_png(&area).map_err(|e| format!("Crop error: {}", e))
+}
+
+/// Crop each selected area, keeping the shape of the current selection and
+/// obscuring the redacted pixels, with the position it was cropped at
+fn crop_redacted_areas(
+    canvas: &Canvas,
+    screenshot: &Screenshot,
+    annotations: &[Annotation],
+) -> Vec<(Pixbuf, (i32, i32))> {
+    let regions = canvas.get_crop_regions();
+    let mask = canvas.get_crop_mask();
+    let last = regions.len().saturating_sub(1);
+
+    // Redact the pixels themselves before cropping
+    let redactions: Vec<_> = annotations.iter().filter_map(|a| a.redaction()).collect();
+    let redacted;
+    let screenshot = if redactions.is_empty() {
+        screenshot
+    } else {
+        redacted = screenshot.redacted(&redactions);
+        &redacted
+    };
+
+    regions
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y, w, h))| {
+            let mask = mask.as_deref().filter(|_| i == last);
+            (screenshot.crop_pixbuf(x, y, w, h, mask), (x, y))
+        })
+        .collect()
+}
+
+/// Crop each selected area, keeping the shape of the current selection
+/// and flattening the annotations into it
+fn crop_selected_areas(canvas: &Canvas, screenshot: &Screenshot) -> ScreenshotResult<Vec<Pixbuf>> {
+    let annotations = canvas.get_annotations();
+    crop_redacted_areas(canvas, screenshot, &annotations)
+        .into_iter()
+        .map(|(area, (x, y))| {
+            if annotations.is_empty() {
+                return Ok(area);
+            }
+            screenshot::flatten(&area, |cr| {
+                cr.translate(-x as f64, -y as f64);
+                annotate::draw_annotations(cr, &annotations);
+            })
+            .map_err(|e| format!("Crop error: {}", e))
+        })
+        .collect()
+}
+
+/// Render the selection in the export format, with the annotations and
+/// callouts drawn as vectors. Several selected areas are laid out on one
+/// page, or rendered one by one when they are exported separately.
+fn export_selected_areas(
+    canvas: &Canvas,
+    screenshot: &Screenshot,
+    format: ExportFormat,
+    callouts: &Callouts,
+) -> ScreenshotResult<Vec<Vec<u8>>> {
+    let annotations = canvas.get_annotations();
+    let style = canvas.beautify();
+    let areas = crop_redacted_areas(canvas, screenshot, &annotations);
+
+    // Draw the annotations of each area, given as its region on screen and
+    // its position in the image
+    let render = |image: &Pixbuf, placed: &[((i32, i32, i32, i32), (i32, i32))]| {
+        vector::render(image, format, callouts, style.as_ref(), |cr| {
+            for &((x, y, w, h), (px, py)) in placed {
+                cr.save().ok();
+                cr.rectangle(px as f64, py as f64, w as f64, h as f64);
+                cr.clip();
+                cr.translate((px - x) as f64, (py - y) as f64);
+                annotate::draw_annotations(cr, &annotations);
+                cr.restore().ok();
+            }
+        })
+        .map_err(|e| format!("Export error: {}", e))
+    };
+
+    let (mode, background) = canvas.multi_export();
+    if areas.len() > 1 && mode != MultiExport::Separate {
+        let regions = canvas.get_crop_regions();
+        let (size, positions) = compose::layout(&regions, mode == MultiExport::Packed);
+        let parts: Vec<_> = areas
+            .into_iter()
+            .map(|(area, _)| area)
+            .zip(positions.iter().copied())
+            .collect();
+        let image = Screenshot::compose(&parts, size, background)
+            .map_err(|e| format!("Export error: {}", e))?;
+        let placed: Vec<_> = regions.into_iter().zip(positions).collect();
+        return Ok(vec![render(&image, &placed)?]);
+    }
+
+    areas
+        .iter()
+        .map(|(area, (x, y))| render(area, &[((*x, *y, area.width(), area.height()), (0, 0))]))
+        .collect()
+}
+
+/// Crop and get PNG data from canvas selection. Several selected areas
+/// are laid out on one image.
 fn get_cropped_png(canvas: &Canvas, screenshot: &Screenshot) -> ScreenshotResult<Vec<u8>> {
-    let (x, y, w, h) = canvas
-        .get_crop_region()
-        .ok_or_else(|| "No selection".to_string())?;
-    screenshot
-        .crop(x, y, w, h)
-        .map_err(|e| format!("Crop error: {}", e))
+    let regions = canvas.get_crop_regions();
+    let mut areas = crop_selected_areas(canvas, screenshot)?;
+    let image = if regions.len() > 1 {
+        let (mode, background) = canvas.multi_export();
+        let (size, positions) = compose::layout(&regions, mode == MultiExport::Packed);
+        let parts: Vec<_> = areas.into_iter().zip(positions).collect();
+        Screenshot::compose(&parts, size, background).map_err(|e| format!("Crop error: {}", e))?
+    } else {
+        areas.pop().ok_or_else(|| "No selection".to_string())?
+    };
+    let image = beautify_image(canvas.beautify().as_ref(), image)?;
+    screenshot::encode_png(&image).map_err(|e| format!("Crop error: {}", e))
+}
+
+/// Decorate an exported image with a beautify style, if beautify is on
+fn beautify_image(style: Option<&Style>, image: Pixbuf) -> ScreenshotResult<Pixbuf> {
+    match style {
+        Some(style) => beautify::apply(&image, style).map_err(|e| format!("Beautify error: {}", e)),
+        None => Ok(image),
+    }
+}
+
+/// Write image data to a new file with the format's extension in ~/Pictures
+fn save_image(data: &[u8], format: ExportFormat) -> ScreenshotResult<PathBuf> {
+    let path = generate_screenshot_path(format.extension())
+        .ok_or_else(|| "Could not determine save path".to_string())?;
+    std::fs::write(&path, data).map_err(|e| format!("Save error: {}", e))?;
+    Ok(path)
+}
+
+/// Save the whole capture with the selection, predefined regions and output
+/// layout to a new project in ~/Pictures, to reopen with `waysnip open`
+fn save_session(canvas: &Canvas, screenshot: &Screenshot) -> ScreenshotResult<PathBuf> {
+    let path = generate_screenshot_path(session::EXTENSION)
+        .ok_or_else(|| "Could not determine save path".to_string())?;
+    let session = Session {
+        screenshot: screenshot.clone(),
+        selection: canvas.selection_rect(),
+        regions: canvas.predefined_regions(),
+    };
+    session.save(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Remember the exported selection so later runs can reuse it
+fn remember_selection(canvas: &Canvas, screenshot: &Screenshot) {
+    let Some((x, y, w, h)) = canvas.get_crop_region() else {
+        return;
+    };
+    let last_region = LastRegion {
+        rect: selection::Rect::new(x as f32, y as f32, w as f32, h as f32),
+        outputs: screenshot.outputs.clone(),
+    };
+    if let Err(e) = last_region.save() {
+        eprintln!("{}", e);
+    }
 }
 
 /// Copy current selection to clipboard
 fn copy_selection_to_clipboard(canvas: &Canvas, screenshot: &Screenshot) -> ScreenshotResult<()> {
     let png_data = get_cropped_png(canvas, screenshot)?;
-    clipboard::copy_image_to_clipboard(&png_data).map_err(|e| format!("Clipboard error: {}", e))
+    clipboard::copy_image_to_clipboard(&png_data).map_err(|e| format!("Clipboard error: {}", e))?;
+    remember_selection(canvas, screenshot);
+    Ok(())
 }
 
-/// Save current selection to file
-fn save_selection_to_file(canvas: &Canvas, screenshot: &Screenshot) -> ScreenshotResult<PathBuf> {
-    let png_data = get_cropped_png(canvas, screenshot)?;
-    let path =
-        generate_screenshot_path().ok_or_else(|| "Could not determine save path".to_string())?;
-    std::fs::write(&path, &png_data).map_err(|e| format!("Save error: {}", e))?;
-    Ok(path)
+/// Save current selection to file, or one file per area when several
+/// areas are exported separately, in the export format and with callouts
+fn save_selection_to_file(
+    canvas: &Canvas,
+    screenshot: &Screenshot,
+) -> ScreenshotResult<Vec<PathBuf>> {
+    let separate = canvas.multi_export().0 == MultiExport::Separate;
+    let (format, callouts) = canvas.export();
+    let paths = if format != ExportFormat::Png || !callouts.is_empty() {
+        export_selected_areas(canvas, screenshot, format, &callouts)?
+            .iter()
+            .map(|data| save_image(data, format))
+            .collect::<ScreenshotResult<_>>()?
+    } else if separate && canvas.get_crop_regions().len() > 1 {
+        let style = canvas.beautify();
+        crop_selected_areas(canvas, screenshot)?
+            .into_iter()
+            .map(|area| {
+                let area = beautify_image(style.as_ref(), area)?;
+                let png_data =
+                    screenshot::encode_png(&area).map_err(|e| format!("Crop error: {}", e))?;
+                save_image(&png_data, ExportFormat::Png)
+            })
+            .collect::<ScreenshotResult<_>>()?
+    } else {
+        vec![save_image(
+            &get_cropped_png(canvas, screenshot)?,
+            ExportFormat::Png,
+        )?]
+    };
+    remember_selection(canvas, screenshot);
+    Ok(paths)
+}
+
+/// Load the last exported region and map it onto the current output layout
+fn resolve_last_region(screenshot: &Screenshot) -> ScreenshotResult<selection::Rect> {
+    let last_region = LastRegion::load()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "No region has been saved yet".to_string())?;
+    last_region
+        .resolve(
+            &screenshot.outputs,
+            screenshot.width as f32,
+            screenshot.height as f32,
+        )
+        .ok_or_else(|| "The last region doesn't fit the current output layout".to_string())
+}
+
+/// Place a named region from the config on the current output layout
+fn resolve_named_region(
+    config: &Config,
+    name: &str,
+    screenshot: &Screenshot,
+) -> ScreenshotResult<selection::Rect> {
+    let region = config
+        .region(name)
+        .ok_or_else(|| format!("No region named \"{}\" in the config", name))?;
+    region
+        .spec
+        .resolve(
+            &screenshot.outputs,
+            screenshot.width as f32,
+            screenshot.height as f32,
+        )
+        .ok_or_else(|| match &region.spec {
+            RegionSpec::Output(output)
+            | RegionSpec::Center {
+                output: Some(output),
+                ..
+            } => format!(
+                "Region \"{}\" is on output {}, which isn't connected",
+                name, output
+            ),
+            _ => format!("Region \"{}\" lies outside the screen", name),
+        })
+}
+
+/// Capture the last exported region, or a named one, without showing the overlay
+fn capture_without_overlay(args: &cli::Args) -> ScreenshotResult<()> {
+    let screenshot = Screenshot::capture().map_err(|e| format!("Screenshot failed: {}", e))?;
+    let config = Config::load();
+    let rect = match args.region {
+        Some(ref name) => resolve_named_region(&config, name, &screenshot)?,
+        None => resolve_last_region(&screenshot)?,
+    };
+    let region = (
+        rect.x.round() as i32,
+        rect.y.round() as i32,
+        rect.width.round() as i32,
+        rect.height.round() as i32,
+    );
+
+    let format = args.format.unwrap_or_default();
+    let callouts = command_line_callouts(args, &config);
+    let style = config.beautify_style();
+    if args.save && (format != ExportFormat::Png || !callouts.is_empty()) {
+        let (x, y, w, h) = region;
+        let area = screenshot.crop_pixbuf(x, y, w, h, None);
+        let data = vector::render(&area, format, &callouts, style.as_ref(), |_| {})
+            .map_err(|e| format!("Export error: {}", e))?;
+        let path = save_image(&data, format)?;
+        eprintln!("Saved to: {}", path.display());
+        return Ok(());
+    }
+
+    let png_data = crop_region_png(&screenshot, region, style.as_ref())?;
+    if args.save {
+        let path = save_image(&png_data, ExportFormat::Png)?;
+        eprintln!("Saved to: {}", path.display());
+        Ok(())
+    } else {
+        clipboard::copy_image_to_clipboard(&png_data).map_err(|e| format!("Clipboard error: {}", e))
+    }
+}
+
+/// Callouts given on the command line, with boxes in the first annotation
+/// color
+fn command_line_callouts(args: &cli::Args, config: &Config) -> Callouts {
+    Callouts {
+        boxes: args.boxes.clone(),
+        color: config
+            .annotation_colors
+            .first()
+            .copied()
+            .unwrap_or(annotate::DEFAULT_COLORS[0]),
+        width: config.annotation_width,
+        caption: args.caption.clone(),
+        border: args.border.unwrap_or(0.0),
+    }
 }
 
 fn main() -> glib::ExitCode {
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    if args.help {
+        println!("{}", cli::USAGE);
+        return glib::ExitCode::SUCCESS;
+    }
+
+    // Non-interactive capture doesn't need GTK at all
+    if args.is_non_interactive() {
+        return match capture_without_overlay(&args) {
+            Ok(()) => glib::ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                glib::ExitCode::FAILURE
+            }
+        };
+    }
+
     // Read predefined regions from stdin BEFORE GTK takes over
-    // This must happen early because GTK may interfere with stdin
-    let predefined_regions = read_predefined_regions_from_stdin();
+    // This must happen early because GTK may interfere with stdin. A reopened
+    // session brings its own regions.
+    let predefined_regions = if args.open.is_none() {
+        read_predefined_regions_from_stdin()
+    } else {
+        Vec::new()
+    };
 
     // Store in a thread-local for the GTK callback to access
     thread_local! {
-        static PREDEFINED_REGIONS: RefCell<Vec<selection::Rect>> = RefCell::new(Vec::new());
+        static PREDEFINED_REGIONS: RefCell<Vec<PredefinedRegion>> = RefCell::new(Vec::new());
     }
     PREDEFINED_REGIONS.with(|r| {
         *r.borrow_mut() = predefined_regions;
@@ -103,10 +447,12 @@ fn main() -> glib::ExitCode {
 
     app.connect_activate(move |app| {
         let regions = PREDEFINED_REGIONS.with(|r| r.borrow().clone());
-        build_ui(app, regions);
+        build_ui(app, regions, &args);
     });
 
-    app.run()
+    // Our own arguments were handled above, don't let GTK parse them
+    let program: Vec<String> = std::env::args().take(1).collect();
+    app.run_with_args(&program)
 }
 
 /// Show a fatal error dialog and quit the application
@@ -138,7 +484,8 @@ fn create_button_css() -> gtk4::CssProvider {
             border: 1px solid rgba(255, 255, 255, 0.1);
             box-shadow: 0 4px 12px rgba(0, 0, 0, 0.4);
         }
-        .button-container button.circular {
+        .button-container button.circular,
+        .button-container menubutton.circular > button {
             min-width: 40px;
             min-height: 40px;
             padding: 3px;
@@ -149,10 +496,12 @@ fn create_button_css() -> gtk4::CssProvider {
             box-shadow: none;
             transition: background-color 200ms ease;
         }
-        .button-container button.circular:hover {
+        .button-container button.circular:hover,
+        .button-container menubutton.circular > button:hover {
             background-color: rgba(255, 255, 255, 0.15);
         }
-        .button-container button.circular:active {
+        .button-container button.circular:active,
+        .button-container menubutton.circular > button:checked {
             background-color: rgba(255, 255, 255, 0.2);
         }
         .button-container button.circular.suggested-action {
@@ -180,8 +529,21 @@ fn create_button_css() -> gtk4::CssProvider {
     css_provider
 }
 
-/// Create the button container with copy, save, and cancel buttons
-fn create_button_container() -> (gtk4::Box, gtk4::Button, gtk4::Button, gtk4::Button) {
+/// Floating toolbar shown next to the selection
+struct Toolbar {
+    container: gtk4::Box,
+    copy_btn: gtk4::Button,
+    save_btn: gtk4::Button,
+    export_btn: gtk4::MenuButton,
+    beautify_btn: gtk4::ToggleButton,
+    cancel_btn: gtk4::Button,
+    preset_btn: gtk4::MenuButton,
+    annotate_btn: gtk4::MenuButton,
+}
+
+/// Create the button container with copy, save, export, beautify, cancel,
+/// preset and annotation buttons
+fn create_button_container() -> Toolbar {
     let button_container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
     button_container.set_visible(false);
     button_container.add_css_class("button-container");
@@ -204,6 +566,26 @@ fn create_button_container() -> (gtk4::Box, gtk4::Button, gtk4::Button, gtk4::Bu
     save_btn.add_css_class("circular");
     save_btn.set_tooltip_text(Some("Save to file"));
 
+    let export_btn = gtk4::MenuButton::new();
+    export_btn.set_icon_name("document-save-as-symbolic");
+    export_btn.add_css_class("circular");
+    export_btn.set_tooltip_text(Some("Save format, border, caption and beautify preset"));
+
+    let beautify_btn = gtk4::ToggleButton::new();
+    beautify_btn.set_icon_name("starred-symbolic");
+    beautify_btn.add_css_class("circular");
+    beautify_btn.set_tooltip_text(Some("Beautify: padding, background and shadow"));
+
+    let preset_btn = gtk4::MenuButton::new();
+    preset_btn.set_icon_name("zoom-fit-best-symbolic");
+    preset_btn.add_css_class("circular");
+    preset_btn.set_tooltip_text(Some("Selection size"));
+
+    let annotate_btn = gtk4::MenuButton::new();
+    annotate_btn.set_icon_name("document-edit-symbolic");
+    annotate_btn.add_css_class("circular");
+    annotate_btn.set_tooltip_text(Some("Annotate"));
+
     let cancel_btn = gtk4::Button::from_icon_name("window-close-symbolic");
     cancel_btn.add_css_class("circular");
     cancel_btn.add_css_class("destructive-action");
@@ -211,10 +593,306 @@ fn create_button_container() -> (gtk4::Box, gtk4::Button, gtk4::Button, gtk4::Bu
 
     button_box.append(&copy_btn);
     button_box.append(&save_btn);
+    button_box.append(&export_btn);
+    button_box.append(&beautify_btn);
+    button_box.append(&preset_btn);
+    button_box.append(&annotate_btn);
     button_box.append(&cancel_btn);
     button_container.append(&button_box);
 
-    (button_container, copy_btn, save_btn, cancel_btn)
+    Toolbar {
+        container: button_container,
+        copy_btn,
+        save_btn,
+        export_btn,
+        beautify_btn,
+        cancel_btn,
+        preset_btn,
+        annotate_btn,
+    }
+}
+
+/// Fill the preset menu with "Free" plus one entry per size preset
+fn setup_preset_menu(preset_btn: &gtk4::MenuButton, canvas: &Canvas, presets: &[SizePreset]) {
+    let popover = gtk4::Popover::new();
+    let list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+
+    let labels = std::iter::once("Free".to_string()).chain(
+        presets
+            .iter()
+            .enumerate()
+            .map(|(i, p)| format!("{}  {}", i + 1, p.label)),
+    );
+
+    for (i, label) in labels.enumerate() {
+        let item = gtk4::Button::with_label(&label);
+        item.add_css_class("flat");
+        if let Some(child) = item.child() {
+            child.set_halign(gtk4::Align::Start);
+        }
+
+        // Entry 0 is "Free", the rest map to preset indices
+        let preset = i.checked_sub(1);
+        let canvas_weak = canvas.downgrade();
+        let popover_weak = popover.downgrade();
+        item.connect_clicked(move |_| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.set_active_preset(preset);
+            }
+            if let Some(popover) = popover_weak.upgrade() {
+                popover.popdown();
+            }
+        });
+        list.append(&item);
+    }
+
+    popover.set_child(Some(&list));
+    preset_btn.set_popover(Some(&popover));
+}
+
+/// Fill the annotation menu with the tools, the palette and stroke width
+/// buttons
+fn setup_annotation_menu(annotate_btn: &gtk4::MenuButton, canvas: &Canvas, palette: &[[u8; 3]]) {
+    let popover = gtk4::Popover::new();
+    let list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+
+    let shortcuts = [
+        "A", "B", "E", "D", "I", "H", "X", "N", "U", "V", "K", "K", "K",
+    ];
+    for (tool, shortcut) in AnnotationTool::ALL.into_iter().zip(shortcuts) {
+        let item = gtk4::Button::with_label(&format!("{}  {}", shortcut, tool.label()));
+        item.add_css_class("flat");
+        if let Some(child) = item.child() {
+            child.set_halign(gtk4::Align::Start);
+        }
+
+        let canvas_weak = canvas.downgrade();
+        let popover_weak = popover.downgrade();
+        item.connect_clicked(move |_| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.annotation_tool() != Some(tool) {
+                    canvas.toggle_annotation_tool(tool);
+                }
+            }
+            if let Some(popover) = popover_weak.upgrade() {
+                popover.popdown();
+            }
+        });
+        list.append(&item);
+    }
+
+    // One swatch per palette color
+    let colors = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
+    for (i, &[r, g, b]) in palette.iter().enumerate() {
+        let swatch = gtk4::DrawingArea::new();
+        swatch.set_content_width(16);
+        swatch.set_content_height(16);
+        swatch.set_draw_func(move |_, cr, width, height| {
+            cr.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+            cr.arc(
+                width as f64 / 2.0,
+                height as f64 / 2.0,
+                width.min(height) as f64 / 2.0,
+                0.0,
+                std::f64::consts::TAU,
+            );
+            cr.fill().ok();
+        });
+        let item = gtk4::Button::new();
+        item.set_child(Some(&swatch));
+        item.add_css_class("flat");
+
+        let canvas_weak = canvas.downgrade();
+        item.connect_clicked(move |_| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.set_annotation_color(i);
+            }
+        });
+        colors.append(&item);
+    }
+    list.append(&colors);
+
+    // Stroke width
+    let widths = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
+    for (label, increase) in [("[  Thinner", false), ("]  Thicker", true)] {
+        let item = gtk4::Button::with_label(label);
+        item.add_css_class("flat");
+        let canvas_weak = canvas.downgrade();
+        item.connect_clicked(move |_| {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.adjust_annotation_width(increase);
+            }
+        });
+        widths.append(&item);
+    }
+    list.append(&widths);
+
+    // Font family, size and background box of text
+    let mut families: Vec<String> = canvas
+        .pango_context()
+        .list_families()
+        .iter()
+        .map(|family| family.name().to_string())
+        .collect();
+    families.sort_by_key(|name| name.to_lowercase());
+    let names: Vec<&str> = families.iter().map(String::as_str).collect();
+    let font = gtk4::DropDown::from_strings(&names);
+    font.set_enable_search(true);
+    font.set_tooltip_text(Some("Font"));
+    let font_size = gtk4::SpinButton::with_range(
+        annotate::MIN_FONT_SIZE as f64,
+        annotate::MAX_FONT_SIZE as f64,
+        1.0,
+    );
+    font_size.set_tooltip_text(Some("Text size"));
+    let background = gtk4::CheckButton::with_label("Background");
+
+    // Show the current style each time the menu opens
+    let canvas_weak = canvas.downgrade();
+    let (font_weak, font_size_weak, background_weak) = (
+        font.downgrade(),
+        font_size.downgrade(),
+        background.downgrade(),
+    );
+    popover.connect_show(move |_| {
+        let (Some(canvas), Some(font), Some(font_size), Some(background)) = (
+            canvas_weak.upgrade(),
+            font_weak.upgrade(),
+            font_size_weak.upgrade(),
+            background_weak.upgrade(),
+        ) else {
+            return;
+        };
+        let (family, size, has_background) = canvas.text_style();
+        if let Some(index) = families.iter().position(|name| *name == family) {
+            font.set_selected(index as u32);
+        }
+        font_size.set_value(size as f64);
+        background.set_active(has_background);
+    });
+
+    let canvas_weak = canvas.downgrade();
+    font.connect_selected_item_notify(move |font| {
+        let name = font
+            .selected_item()
+            .and_downcast::<gtk4::StringObject>()
+            .map(|item| item.string());
+        if let (Some(canvas), Some(name)) = (canvas_weak.upgrade(), name) {
+            if canvas.text_style().0 != name.as_str() {
+                canvas.set_text_font(&name);
+            }
+        }
+    });
+    let canvas_weak = canvas.downgrade();
+    font_size.connect_value_changed(move |font_size| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            canvas.set_text_size(font_size.value() as f32);
+        }
+    });
+    let canvas_weak = canvas.downgrade();
+    background.connect_toggled(move |background| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            canvas.set_text_background(background.is_active());
+        }
+    });
+
+    let text = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    text.append(&font);
+    text.append(&font_size);
+    list.append(&text);
+    list.append(&background);
+
+    popover.set_child(Some(&list));
+    annotate_btn.set_popover(Some(&popover));
+}
+
+/// Fill the export menu with the format of saved images, the border and
+/// caption added to them and the beautify preset
+fn setup_export_menu(export_btn: &gtk4::MenuButton, canvas: &Canvas) {
+    let popover = gtk4::Popover::new();
+    let list = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+    let (current, callouts) = canvas.export();
+
+    let names: Vec<String> = ExportFormat::ALL
+        .iter()
+        .map(|f| f.extension().to_uppercase())
+        .collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let format = gtk4::DropDown::from_strings(&names);
+    format.set_tooltip_text(Some("Format of saved images"));
+    if let Some(index) = ExportFormat::ALL.iter().position(|f| *f == current) {
+        format.set_selected(index as u32);
+    }
+    let canvas_weak = canvas.downgrade();
+    format.connect_selected_notify(move |format| {
+        let selected = ExportFormat::ALL.get(format.selected() as usize);
+        if let (Some(canvas), Some(&selected)) = (canvas_weak.upgrade(), selected) {
+            let (_, callouts) = canvas.export();
+            canvas.set_export(selected, callouts);
+        }
+    });
+
+    let border = gtk4::SpinButton::with_range(0.0, vector::MAX_BORDER as f64, 1.0);
+    border.set_tooltip_text(Some("Border width"));
+    border.set_value(callouts.border as f64);
+    let canvas_weak = canvas.downgrade();
+    border.connect_value_changed(move |border| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            let (format, mut callouts) = canvas.export();
+            callouts.border = border.value() as f32;
+            canvas.set_export(format, callouts);
+        }
+    });
+    let border_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    border_row.append(&gtk4::Label::new(Some("Border")));
+    border_row.append(&border);
+
+    let caption = gtk4::Entry::new();
+    caption.set_placeholder_text(Some("Caption"));
+    caption.set_text(callouts.caption.as_deref().unwrap_or(""));
+    let canvas_weak = canvas.downgrade();
+    caption.connect_changed(move |caption| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            let (format, mut callouts) = canvas.export();
+            let text = caption.text();
+            callouts.caption = (!text.trim().is_empty()).then(|| text.to_string());
+            canvas.set_export(format, callouts);
+        }
+    });
+
+    let names: Vec<String> = canvas
+        .beautify_presets()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let preset = gtk4::DropDown::from_strings(&names);
+    preset.set_tooltip_text(Some("Beautify preset"));
+    preset.set_selected(canvas.beautify_preset() as u32);
+    let canvas_weak = canvas.downgrade();
+    preset.connect_selected_notify(move |preset| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            canvas.set_beautify_preset(preset.selected() as usize);
+        }
+    });
+
+    list.append(&format);
+    list.append(&border_row);
+    list.append(&caption);
+    list.append(&preset);
+    popover.set_child(Some(&list));
+    export_btn.set_popover(Some(&popover));
+}
+
+/// Turn beautifying exports on and off with the toolbar toggle
+fn setup_beautify_toggle(beautify_btn: &gtk4::ToggleButton, canvas: &Canvas) {
+    beautify_btn.set_active(canvas.beautify_enabled());
+    let canvas_weak = canvas.downgrade();
+    beautify_btn.connect_toggled(move |button| {
+        if let Some(canvas) = canvas_weak.upgrade() {
+            canvas.set_beautify_enabled(button.is_active());
+        }
+    });
 }
 
 /// Setup the selection change callback to update button position
@@ -286,6 +964,7 @@ fn setup_keyboard_shortcuts(
     window: &gtk4::ApplicationWindow,
     canvas: &Canvas,
     screenshot_data: &Rc<RefCell<Screenshot>>,
+    hint_action: HintAction,
 ) {
     let key_controller = gtk4::EventControllerKey::new();
     let window_weak = window.downgrade();
@@ -294,6 +973,46 @@ fn setup_keyboard_shortcuts(
 
     key_controller.connect_key_pressed(move |_, key, _, modifier| {
         let ctrl = modifier.contains(gdk::ModifierType::CONTROL_MASK);
+        let shift = modifier.contains(gdk::ModifierType::SHIFT_MASK);
+
+        // While hints are shown, letters type a hint and Esc leaves hint mode
+        if let Some(canvas) = canvas_weak.upgrade().filter(|c| !ctrl && c.hints_active()) {
+            let letter = key.to_unicode().filter(|c| c.is_ascii_alphabetic());
+            if key == gdk::Key::Escape {
+                canvas.hide_hints();
+            } else if key == gdk::Key::BackSpace {
+                canvas.hint_backspace();
+            } else if let Some(letter) = letter {
+                if let HintInput::Matched(_) = canvas.type_hint(letter) {
+                    let screenshot = screenshot_ref.borrow();
+                    let exported = match hint_action {
+                        HintAction::Select => false,
+                        HintAction::Copy => {
+                            if let Err(e) = copy_selection_to_clipboard(&canvas, &screenshot) {
+                                eprintln!("{}", e);
+                            }
+                            true
+                        }
+                        HintAction::Save => {
+                            match save_selection_to_file(&canvas, &screenshot) {
+                                Ok(paths) => {
+                                    for path in paths {
+                                        eprintln!("Saved to: {}", path.display());
+                                    }
+                                }
+                                Err(e) => eprintln!("{}", e),
+                            }
+                            true
+                        }
+                    };
+                    drop(screenshot);
+                    if let (true, Some(win)) = (exported, window_weak.upgrade()) {
+                        win.close();
+                    }
+                }
+            }
+            return glib::Propagation::Stop;
+        }
 
         // ESC to cancel
         if key == gdk::Key::Escape {
@@ -311,6 +1030,190 @@ fn setup_keyboard_shortcuts(
             return glib::Propagation::Stop;
         }
 
+        // Ctrl+Z to undo, Ctrl+Shift+Z to redo
+        if ctrl && (key == gdk::Key::z || key == gdk::Key::Z) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if shift {
+                    canvas.redo();
+                } else {
+                    canvas.undo();
+                }
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // Arrow keys to nudge the selection by 1px, or 10px with Shift
+        let step = if shift { 10.0 } else { 1.0 };
+        let nudge = match key {
+            gdk::Key::Left => Some((-step, 0.0)),
+            gdk::Key::Right => Some((step, 0.0)),
+            gdk::Key::Up => Some((0.0, -step)),
+            gdk::Key::Down => Some((0.0, step)),
+            _ => None,
+        };
+        if let Some((dx, dy)) = nudge {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.nudge(dx, dy);
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // R to restore the last exported region
+        if !ctrl && (key == gdk::Key::r || key == gdk::Key::R) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                match resolve_last_region(&screenshot_ref.borrow()) {
+                    Ok(rect) => canvas.set_selection(rect),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // F to show letter hints over regions and outputs
+        if !ctrl && (key == gdk::Key::f || key == gdk::Key::F) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.show_hints();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // W to toggle the magic wand, [ and ] to change its tolerance (or
+        // the annotation stroke width)
+        if !ctrl && (key == gdk::Key::w || key == gdk::Key::W) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.toggle_wand_mode();
+            }
+            return glib::Propagation::Stop;
+        }
+        if !ctrl && (key == gdk::Key::bracketleft || key == gdk::Key::bracketright) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.annotation_tool().is_some() {
+                    canvas.adjust_annotation_width(key == gdk::Key::bracketright);
+                } else {
+                    canvas.adjust_wand_tolerance(key == gdk::Key::bracketright);
+                }
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // A, B, E, D, I, H, X, N, U and V to draw arrows, boxes, ellipses,
+        // lines, pen and highlighter strokes, text, step markers, spotlights
+        // and magnified insets, C to change their color
+        let tool = match key {
+            gdk::Key::a | gdk::Key::A => Some(AnnotationTool::Arrow),
+            gdk::Key::b | gdk::Key::B => Some(AnnotationTool::Rectangle),
+            gdk::Key::e | gdk::Key::E => Some(AnnotationTool::Ellipse),
+            gdk::Key::d | gdk::Key::D => Some(AnnotationTool::Line),
+            gdk::Key::i | gdk::Key::I => Some(AnnotationTool::Pen),
+            gdk::Key::h | gdk::Key::H => Some(AnnotationTool::Highlighter),
+            gdk::Key::x | gdk::Key::X => Some(AnnotationTool::Text),
+            gdk::Key::n | gdk::Key::N => Some(AnnotationTool::Step),
+            gdk::Key::u | gdk::Key::U => Some(AnnotationTool::Spotlight),
+            gdk::Key::v | gdk::Key::V => Some(AnnotationTool::Magnify),
+            _ => None,
+        };
+        if let (false, Some(tool)) = (ctrl, tool) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.toggle_annotation_tool(tool);
+            }
+            return glib::Propagation::Stop;
+        }
+        if !ctrl && (key == gdk::Key::c || key == gdk::Key::C) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.cycle_annotation_color();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // K to blur, pixelate or fill areas, cycling through the styles
+        if !ctrl && (key == gdk::Key::k || key == gdk::Key::K) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.cycle_redact_tool();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // Shift+T to switch between a window with and without its shadow
+        if !ctrl && shift && (key == gdk::Key::t || key == gdk::Key::T) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.toggle_window_shadow();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // T to trim uniform borders from the selection
+        if !ctrl && (key == gdk::Key::t || key == gdk::Key::T) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.trim_selection();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // O, G and L to switch to ellipse, polygon or lasso selections
+        let shape = match key {
+            gdk::Key::o | gdk::Key::O => Some(SelectionShape::Ellipse),
+            gdk::Key::g | gdk::Key::G => Some(SelectionShape::Polygon),
+            gdk::Key::l | gdk::Key::L => Some(SelectionShape::Lasso),
+            _ => None,
+        };
+        if let (false, Some(shape)) = (ctrl, shape) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.toggle_selection_shape(shape);
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // Enter closes the polygon being placed, Backspace removes its last vertex
+        if key == gdk::Key::Return || key == gdk::Key::KP_Enter {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.close_polygon() {
+                    return glib::Propagation::Stop;
+                }
+            }
+        }
+        if key == gdk::Key::BackSpace {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.remove_polygon_vertex() {
+                    return glib::Propagation::Stop;
+                }
+            }
+        }
+
+        // Tab / Shift+Tab to cycle through overlapping regions under the cursor
+        if !ctrl && (key == gdk::Key::Tab || key == gdk::Key::ISO_Left_Tab) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                if canvas.cycle_predefined_region(!shift) {
+                    return glib::Propagation::Stop;
+                }
+            }
+        }
+
+        // P to pick a named region from the config
+        if !ctrl && (key == gdk::Key::p || key == gdk::Key::P) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.show_region_menu();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // M to toggle snapping to edges, regions and outputs
+        if !ctrl && (key == gdk::Key::m || key == gdk::Key::M) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                canvas.toggle_edge_snapping();
+            }
+            return glib::Propagation::Stop;
+        }
+
+        // 1-9 to pick a size preset, 0 to go back to free selection
+        if !ctrl {
+            if let Some(digit) = key.to_unicode().and_then(|c| c.to_digit(10)) {
+                if let Some(canvas) = canvas_weak.upgrade() {
+                    canvas.set_active_preset(digit.checked_sub(1).map(|i| i as usize));
+                }
+                return glib::Propagation::Stop;
+            }
+        }
+
         // Ctrl+C to copy
         if ctrl && (key == gdk::Key::c || key == gdk::Key::C) {
             if let Some(canvas) = canvas_weak.upgrade() {
@@ -326,12 +1229,27 @@ fn setup_keyboard_shortcuts(
             return glib::Propagation::Stop;
         }
 
+        // Ctrl+Shift+S to save the session, keeping the overlay open
+        if ctrl && shift && (key == gdk::Key::s || key == gdk::Key::S) {
+            if let Some(canvas) = canvas_weak.upgrade() {
+                match save_session(&canvas, &screenshot_ref.borrow()) {
+                    Ok(path) => eprintln!("Session saved to: {}", path.display()),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            return glib::Propagation::Stop;
+        }
+
         // Ctrl+S to save
         if ctrl && (key == gdk::Key::s || key == gdk::Key::S) {
             if let Some(canvas) = canvas_weak.upgrade() {
                 let screenshot = screenshot_ref.borrow();
                 match save_selection_to_file(&canvas, &screenshot) {
-                    Ok(path) => eprintln!("Saved to: {}", path.display()),
+                    Ok(paths) => {
+                        for path in paths {
+                            eprintln!("Saved to: {}", path.display());
+                        }
+                    }
                     Err(e) => eprintln!("{}", e),
                 }
                 drop(screenshot);
@@ -353,13 +1271,11 @@ fn connect_button_handlers(
     window: &gtk4::ApplicationWindow,
     canvas: &Canvas,
     screenshot_data: &Rc<RefCell<Screenshot>>,
-    copy_btn: &gtk4::Button,
-    save_btn: &gtk4::Button,
-    cancel_btn: &gtk4::Button,
+    toolbar: &Toolbar,
 ) {
     // Cancel button
     let window_weak = window.downgrade();
-    cancel_btn.connect_clicked(move |_| {
+    toolbar.cancel_btn.connect_clicked(move |_| {
         if let Some(w) = window_weak.upgrade() {
             w.close();
         }
@@ -369,7 +1285,7 @@ fn connect_button_handlers(
     let canvas_weak = canvas.downgrade();
     let screenshot_ref = screenshot_data.clone();
     let window_weak = window.downgrade();
-    copy_btn.connect_clicked(move |_| {
+    toolbar.copy_btn.connect_clicked(move |_| {
         if let Some(canvas) = canvas_weak.upgrade() {
             let screenshot = screenshot_ref.borrow();
             if let Err(e) = copy_selection_to_clipboard(&canvas, &screenshot) {
@@ -385,7 +1301,7 @@ fn connect_button_handlers(
     let canvas_weak = canvas.downgrade();
     let screenshot_ref = screenshot_data.clone();
     let window_weak = window.downgrade();
-    save_btn.connect_clicked(move |_| {
+    toolbar.save_btn.connect_clicked(move |_| {
         let Some(canvas) = canvas_weak.upgrade() else {
             return;
         };
@@ -395,7 +1311,11 @@ fn connect_button_handlers(
 
         let screenshot = screenshot_ref.borrow();
         match save_selection_to_file(&canvas, &screenshot) {
-            Ok(path) => eprintln!("Saved to: {}", path.display()),
+            Ok(paths) => {
+                for path in paths {
+                    eprintln!("Saved to: {}", path.display());
+                }
+            }
             Err(e) => eprintln!("{}", e),
         }
         drop(screenshot);
@@ -403,18 +1323,29 @@ fn connect_button_handlers(
     });
 }
 
-fn build_ui(app: &gtk4::Application, predefined_regions: Vec<selection::Rect>) {
+fn build_ui(app: &gtk4::Application, predefined_regions: Vec<PredefinedRegion>, args: &cli::Args) {
     // Force Adwaita icon theme via GTK settings
     let settings = gtk4::Settings::default().expect("Could not get default settings");
     settings.set_gtk_icon_theme_name(Some("Adwaita"));
 
-    // First, capture the screenshot before showing any UI
-    let screenshot = match screenshot::Screenshot::capture() {
-        Ok(s) => s,
-        Err(e) => {
-            show_fatal_error(app, &format!("Screenshot failed: {}", e));
+    // First, capture the screenshot before showing any UI, or load the one
+    // of a saved session with its selection and regions
+    let (screenshot, saved) = match args.open.as_deref().map(Session::load) {
+        Some(Ok(session)) => (
+            session.screenshot,
+            Some((session.selection, session.regions)),
+        ),
+        Some(Err(e)) => {
+            show_fatal_error(app, &e.to_string());
             return;
         }
+        None => match screenshot::Screenshot::capture() {
+            Ok(s) => (s, None),
+            Err(e) => {
+                show_fatal_error(app, &format!("Screenshot failed: {}", e));
+                return;
+            }
+        },
     };
 
     let screen_width = screenshot.width;
@@ -435,13 +1366,99 @@ fn build_ui(app: &gtk4::Application, predefined_regions: Vec<selection::Rect>) {
     // Use a Fixed container for precise positioning
     let fixed = gtk4::Fixed::new();
 
+    let config = Config::load();
+
     // Create canvas and set the screenshot
     let canvas = Canvas::new();
     canvas.set_pixbuf(&screenshot.pixbuf);
+    canvas.set_size_presets(config.size_presets.clone());
+    canvas.set_edge_snapping(config.snap_to_edges);
+    canvas.set_wand_tolerance(config.wand_tolerance);
+    canvas.set_trim_tolerance(config.trim_tolerance);
+    canvas.set_window_shadow(config.window_shadow, config.app_window_shadows.clone());
+    canvas.set_annotation_style(config.annotation_colors.clone(), config.annotation_width);
+    canvas.set_text_style(&config.text_font, config.text_size, config.text_background);
+    canvas.set_step_style(config.step_size, config.step_style);
+    canvas.set_multi_export(config.multi_export, config.multi_background);
+    canvas.set_export(
+        args.format.unwrap_or_default(),
+        command_line_callouts(args, &config),
+    );
+    canvas.set_beautify(
+        config.beautify_presets.clone(),
+        config.beautify_preset,
+        config.beautify,
+    );
+    canvas.set_output_regions(screenshot.outputs.iter().map(|o| o.rect).collect());
+
+    // Offer the named regions from the config in the quick-pick menu
+    let named_regions = config
+        .regions
+        .iter()
+        .filter_map(|r| {
+            let rect = r.spec.resolve(
+                &screenshot.outputs,
+                screen_width as f32,
+                screen_height as f32,
+            )?;
+            Some((r.name.clone(), rect))
+        })
+        .collect();
+    canvas.set_named_regions(named_regions);
+
+    // Start with the saved selection of a session, or the last exported
+    // region if configured
+    if let Some((selection, _)) = &saved {
+        if let Some(rect) = *selection {
+            canvas.set_selection(rect);
+        }
+    } else if config.restore_last_region {
+        match resolve_last_region(&screenshot) {
+            Ok(rect) => canvas.set_selection(rect),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
 
-    // Set predefined regions if any were provided via stdin
-    if !predefined_regions.is_empty() {
+    // Set predefined regions saved with a session or provided via stdin,
+    // otherwise ask the compositor for its windows and outputs, or look for
+    // them in the screenshot
+    if let Some((_, regions)) = saved {
+        canvas.set_predefined_regions(regions);
+    } else if !predefined_regions.is_empty() {
         canvas.set_predefined_regions(predefined_regions);
+    } else {
+        // Ask the compositor off the main thread so a slow socket doesn't
+        // hold up the overlay, and only look in the screenshot when there's
+        // no compositor to ask, or asking it failed
+        let outputs: Vec<_> = screenshot
+            .outputs
+            .iter()
+            .map(|o| PredefinedRegion::labelled(o.rect, o.name.clone()))
+            .collect();
+        let detect_regions = config.detect_regions;
+        match ipc::Compositor::detect() {
+            Some(compositor) if config.window_regions => {
+                let canvas_weak = canvas.downgrade();
+                glib::spawn_future_local(async move {
+                    let regions = gio::spawn_blocking(move || {
+                        compositor.regions().map_err(|e| eprintln!("{}", e)).ok()
+                    })
+                    .await
+                    .ok()
+                    .flatten();
+                    let Some(canvas) = canvas_weak.upgrade() else {
+                        return;
+                    };
+                    match regions {
+                        Some(regions) => canvas.set_predefined_regions(regions),
+                        None if detect_regions => canvas.detect_regions(outputs),
+                        None => {}
+                    }
+                });
+            }
+            None if detect_regions => canvas.detect_regions(outputs),
+            _ => {}
+        }
     }
 
     canvas.setup_controllers();
@@ -449,7 +1466,11 @@ fn build_ui(app: &gtk4::Application, predefined_regions: Vec<selection::Rect>) {
     fixed.put(&canvas, 0.0, 0.0);
 
     // Create button container
-    let (button_container, copy_btn, save_btn, cancel_btn) = create_button_container();
+    let toolbar = create_button_container();
+    setup_preset_menu(&toolbar.preset_btn, &canvas, &config.size_presets);
+    setup_annotation_menu(&toolbar.annotate_btn, &canvas, &config.annotation_colors);
+    setup_export_menu(&toolbar.export_btn, &canvas);
+    setup_beautify_toggle(&toolbar.beautify_btn, &canvas);
 
     // Apply CSS styling
     let css_provider = create_button_css();
@@ -460,7 +1481,7 @@ fn build_ui(app: &gtk4::Application, predefined_regions: Vec<selection::Rect>) {
     );
 
     // Add button container to fixed
-    fixed.put(&button_container, 0.0, 0.0);
+    fixed.put(&toolbar.container, 0.0, 0.0);
     window.set_child(Some(&fixed));
 
     // Store screenshot data for later use
@@ -469,20 +1490,17 @@ fn build_ui(app: &gtk4::Application, predefined_regions: Vec<selection::Rect>) {
     // Setup callbacks and handlers
     setup_selection_callback(
         &canvas,
-        &button_container,
+        &toolbar.container,
         &fixed,
         screen_width,
         screen_height,
     );
-    connect_button_handlers(
-        &window,
-        &canvas,
-        &screenshot_data,
-        &copy_btn,
-        &save_btn,
-        &cancel_btn,
-    );
-    setup_keyboard_shortcuts(&window, &canvas, &screenshot_data);
+    connect_button_handlers(&window, &canvas, &screenshot_data, &toolbar);
+    setup_keyboard_shortcuts(&window, &canvas, &screenshot_data, config.hint_action);
+
+    if args.hints && !canvas.show_hints() {
+        eprintln!("No regions to show hints for");
+    }
 
     window.present();
 }
@@ -0,0 +1,94 @@
+//! Pixel masks for non-rectangular selections
+
+use crate::selection::Rect;
+
+/// Pixels covered by a non-rectangular selection, stored over its bounding
+/// box in screen coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    covered: Vec<bool>,
+}
+
+impl Mask {
+    /// Build a mask over the given bounds from a coverage test in screen
+    /// coordinates
+    pub fn from_fn(
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        covered: impl Fn(i32, i32) -> bool,
+    ) -> Self {
+        let mut bits = Vec::with_capacity((width * height).max(0) as usize);
+        for row in y..y + height {
+            for col in x..x + width {
+                bits.push(covered(col, row));
+            }
+        }
+        Self {
+            x,
+            y,
+            width,
+            height,
+            covered: bits,
+        }
+    }
+
+    /// Cover the ellipse inscribed in the given bounds
+    pub fn ellipse(x: i32, y: i32, width: i32, height: i32) -> Self {
+        let (rx, ry) = (width as f32 / 2.0, height as f32 / 2.0);
+        let (cx, cy) = (x as f32 + rx, y as f32 + ry);
+        Self::from_fn(x, y, width, height, |px, py| {
+            let dx = (px as f32 + 0.5 - cx) / rx;
+            let dy = (py as f32 + 0.5 - cy) / ry;
+            dx * dx + dy * dy <= 1.0
+        })
+    }
+
+    /// Cover the inside of a closed polygon, using the even-odd rule
+    pub fn polygon(x: i32, y: i32, width: i32, height: i32, points: &[(f32, f32)]) -> Self {
+        // Where each pixel row's center line crosses the outline
+        let crossings: Vec<Vec<f32>> = (y..y + height)
+            .map(|py| {
+                let cy = py as f32 + 0.5;
+                let mut xs: Vec<f32> = points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .filter(|&(&(_, y0), &(_, y1))| (y0 <= cy) != (y1 <= cy))
+                    .map(|(&(x0, y0), &(x1, y1))| x0 + (cy - y0) / (y1 - y0) * (x1 - x0))
+                    .collect();
+                xs.sort_by(f32::total_cmp);
+                xs
+            })
+            .collect();
+
+        Self::from_fn(x, y, width, height, |px, py| {
+            let cx = px as f32 + 0.5;
+            let row = &crossings[(py - y) as usize];
+            row.iter().filter(|&&edge| edge < cx).count() % 2 == 1
+        })
+    }
+
+    /// Check if a pixel in screen coordinates is covered
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (col, row) = (x - self.x, y - self.y);
+        if col < 0 || row < 0 || col >= self.width || row >= self.height {
+            return false;
+        }
+        self.covered[(row * self.width + col) as usize]
+    }
+
+    /// The bounding box of the mask
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            self.x as f32,
+            self.y as f32,
+            self.width as f32,
+            self.height as f32,
+        )
+    }
+}
@@ -0,0 +1,89 @@
+//! Read-only pixel access to a screenshot for image analysis
+
+use gdk_pixbuf::Pixbuf;
+
+/// An immutable copy of a pixbuf's pixel data
+#[derive(Clone)]
+pub struct Pixels {
+    pub width: i32,
+    pub height: i32,
+    data: glib::Bytes,
+    rowstride: usize,
+    n_channels: usize,
+}
+
+impl std::fmt::Debug for Pixels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pixels({}x{})", self.width, self.height)
+    }
+}
+
+impl Pixels {
+    /// Take a snapshot of the pixbuf's pixel data
+    pub fn from_pixbuf(pixbuf: &Pixbuf) -> Self {
+        Self {
+            width: pixbuf.width(),
+            height: pixbuf.height(),
+            data: pixbuf.read_pixel_bytes(),
+            rowstride: pixbuf.rowstride() as usize,
+            n_channels: pixbuf.n_channels() as usize,
+        }
+    }
+
+    /// Build pixels from the color of each pixel
+    #[cfg(test)]
+    pub fn from_fn(width: i32, height: i32, color: impl Fn(i32, i32) -> [u8; 3]) -> Self {
+        let mut data = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&color(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            data: glib::Bytes::from_owned(data),
+            rowstride: width as usize * 3,
+            n_channels: 3,
+        }
+    }
+
+    /// Get the RGB color of a pixel; coordinates must be in bounds
+    pub fn rgb(&self, x: i32, y: i32) -> [u8; 3] {
+        let offset = y as usize * self.rowstride + x as usize * self.n_channels;
+        [
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+        ]
+    }
+
+    /// Copy an area as tightly packed RGBA; the area must be in bounds
+    pub fn rgba(&self, x: i32, y: i32, width: i32, height: i32) -> Vec<u8> {
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for row in y..y + height {
+            for col in x..x + width {
+                let [r, g, b] = self.rgb(col, row);
+                data.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        data
+    }
+
+    /// Get the luminance (0-255) of a pixel; coordinates must be in bounds
+    pub fn luma(&self, x: i32, y: i32) -> u8 {
+        let [r, g, b] = self.rgb(x, y);
+        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+    }
+
+    /// Get the luminance of every pixel as a row-major plane
+    pub fn luma_plane(&self) -> Vec<u8> {
+        let mut plane = Vec::with_capacity((self.width * self.height) as usize);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                plane.push(self.luma(x, y));
+            }
+        }
+        plane
+    }
+}
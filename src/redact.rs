@@ -0,0 +1,267 @@
+//! Redaction of sensitive areas by blurring, pixelating or filling them
+//!
+//! Redactions rewrite the pixels themselves, so nothing of the original
+//! content is left in the exported image.
+
+/// Radius of each box blur pass in pixels
+const BLUR_RADIUS: usize = 10;
+
+/// Number of box blur passes, three approximating a gaussian
+const BLUR_PASSES: usize = 3;
+
+/// Side of the squares a pixelated area is made of
+const PIXELATE_BLOCK: i32 = 12;
+
+/// How a redacted area is obscured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactStyle {
+    Blur,
+    /// Squares of the average color of the pixels they cover
+    Pixelate,
+    /// Solid color
+    Fill,
+}
+
+impl RedactStyle {
+    pub fn label(self) -> &'static str {
+        match self {
+            RedactStyle::Blur => "Blur",
+            RedactStyle::Pixelate => "Pixelate",
+            RedactStyle::Fill => "Solid fill",
+        }
+    }
+
+    /// The style after this one, None after the last
+    pub fn next(self) -> Option<Self> {
+        match self {
+            RedactStyle::Blur => Some(RedactStyle::Pixelate),
+            RedactStyle::Pixelate => Some(RedactStyle::Fill),
+            RedactStyle::Fill => None,
+        }
+    }
+}
+
+/// An area to obscure, in screenshot pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redaction {
+    pub region: (i32, i32, i32, i32),
+    pub style: RedactStyle,
+    /// Color of a solid fill
+    pub color: [u8; 3],
+}
+
+impl Redaction {
+    /// Obscure the region in RGBA pixel data of the given size and row
+    /// stride. Only pixels inside the region are read, so redacting a copy
+    /// of the region gives the same result as redacting the whole image.
+    pub fn apply(&self, data: &mut [u8], (width, height): (i32, i32), stride: usize) {
+        let (x, y, w, h) = self.region;
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + w).min(width);
+        let bottom = (y + h).min(height);
+        if left >= right || top >= bottom {
+            return;
+        }
+        let (w, h) = ((right - left) as usize, (bottom - top) as usize);
+        let offset =
+            |col: usize, row: usize| (top as usize + row) * stride + (left as usize + col) * 4;
+
+        let mut pixels: Vec<[u8; 3]> = Vec::with_capacity(w * h);
+        for row in 0..h {
+            for col in 0..w {
+                let i = offset(col, row);
+                pixels.push([data[i], data[i + 1], data[i + 2]]);
+            }
+        }
+
+        match self.style {
+            RedactStyle::Blur => blur(&mut pixels, w, h),
+            RedactStyle::Pixelate => pixelate(&mut pixels, w, h),
+            RedactStyle::Fill => pixels.fill(self.color),
+        }
+
+        for row in 0..h {
+            for col in 0..w {
+                let i = offset(col, row);
+                let [r, g, b] = pixels[row * w + col];
+                data[i..i + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+    }
+}
+
+/// Box blur rows then columns, repeatedly
+fn blur(pixels: &mut [[u8; 3]], width: usize, height: usize) {
+    let mut line = Vec::new();
+    let mut scratch = Vec::new();
+    for _ in 0..BLUR_PASSES {
+        for row in pixels.chunks_exact_mut(width) {
+            blur_line(row, &mut scratch);
+        }
+        for col in 0..width {
+            line.clear();
+            line.extend((0..height).map(|row| pixels[row * width + col]));
+            blur_line(&mut line, &mut scratch);
+            for (row, &pixel) in line.iter().enumerate() {
+                pixels[row * width + col] = pixel;
+            }
+        }
+    }
+}
+
+/// Replace each pixel by the average of its neighbours within the blur
+/// radius, repeating the pixels at the ends
+fn blur_line(line: &mut [[u8; 3]], scratch: &mut Vec<[u8; 3]>) {
+    scratch.clear();
+    scratch.extend_from_slice(line);
+    let last = line.len() as isize - 1;
+    let at = |i: isize| scratch[i.clamp(0, last) as usize];
+    let radius = BLUR_RADIUS as isize;
+    let window = 2 * BLUR_RADIUS as u32 + 1;
+
+    let mut sum = [0u32; 3];
+    for i in -radius..=radius {
+        for (s, v) in sum.iter_mut().zip(at(i)) {
+            *s += v as u32;
+        }
+    }
+    for (i, pixel) in line.iter_mut().enumerate() {
+        *pixel = sum.map(|s| ((s + window / 2) / window) as u8);
+        let (leaving, entering) = (at(i as isize - radius), at(i as isize + radius + 1));
+        for c in 0..3 {
+            sum[c] = sum[c] + entering[c] as u32 - leaving[c] as u32;
+        }
+    }
+}
+
+/// Fill squares with their average color
+fn pixelate(pixels: &mut [[u8; 3]], width: usize, height: usize) {
+    let block = PIXELATE_BLOCK as usize;
+    for top in (0..height).step_by(block) {
+        for left in (0..width).step_by(block) {
+            let rows = top..(top + block).min(height);
+            let cols = left..(left + block).min(width);
+            let mut sum = [0u32; 3];
+            for row in rows.clone() {
+                for pixel in &pixels[row * width + cols.start..row * width + cols.end] {
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as u32;
+                    }
+                }
+            }
+            let count = (rows.len() * cols.len()) as u32;
+            let average = sum.map(|s| ((s + count / 2) / count) as u8);
+            for row in rows {
+                pixels[row * width + cols.start..row * width + cols.end].fill(average);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGBA data with `padding` unused bytes at the end of every row
+    fn image(width: i32, height: i32, padding: usize, color: impl Fn(i32, i32) -> u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let v = color(x, y);
+                data.extend_from_slice(&[v, v, v, 0]);
+            }
+            data.extend(std::iter::repeat_n(7, padding));
+        }
+        data
+    }
+
+    fn redact(region: (i32, i32, i32, i32), style: RedactStyle) -> Redaction {
+        Redaction {
+            region,
+            style,
+            color: [10, 20, 30],
+        }
+    }
+
+    fn pixel(data: &[u8], stride: usize, x: usize, y: usize) -> [u8; 4] {
+        let i = y * stride + x * 4;
+        [data[i], data[i + 1], data[i + 2], data[i + 3]]
+    }
+
+    #[test]
+    fn fill_only_the_region() {
+        let (width, height, stride) = (8, 6, 8 * 4 + 4);
+        let mut data = image(width, height, 4, |_, _| 100);
+        redact((6, 4, 5, 5), RedactStyle::Fill).apply(&mut data, (width, height), stride);
+        for y in 0..6 {
+            for x in 0..8 {
+                let expected = if x >= 6 && y >= 4 {
+                    [10, 20, 30, 255]
+                } else {
+                    [100, 100, 100, 0]
+                };
+                assert_eq!(pixel(&data, stride, x, y), expected, "{},{}", x, y);
+            }
+        }
+        // Row padding is left alone
+        assert!(data.chunks(stride).all(|row| row[32..] == [7; 4]));
+    }
+
+    #[test]
+    fn region_off_the_image() {
+        let mut data = image(8, 6, 0, |_, _| 100);
+        let original = data.clone();
+        redact((8, 0, 4, 4), RedactStyle::Fill).apply(&mut data, (8, 6), 32);
+        redact((-4, -4, 4, 10), RedactStyle::Blur).apply(&mut data, (8, 6), 32);
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn pixelate_averages_blocks() {
+        // Left half black, right half light: the first block straddles both
+        let mut data = image(18, 12, 0, |x, _| if x < 6 { 0 } else { 200 });
+        redact((0, 0, 18, 12), RedactStyle::Pixelate).apply(&mut data, (18, 12), 18 * 4);
+        for y in 0..12 {
+            for x in 0..18 {
+                let v = if x < 12 { 100 } else { 200 };
+                assert_eq!(pixel(&data, 18 * 4, x, y), [v, v, v, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn blur_softens_edges() {
+        let mut data = image(80, 10, 0, |x, _| if x < 40 { 0 } else { 240 });
+        redact((0, 0, 80, 10), RedactStyle::Blur).apply(&mut data, (80, 10), 80 * 4);
+        let row: Vec<u8> = (0..80).map(|x| pixel(&data, 80 * 4, x, 5)[0]).collect();
+        assert!(row.windows(2).all(|w| w[0] <= w[1]), "{:?}", row);
+        assert!(row[39] > 0 && row[40] < 240, "{:?}", row);
+        // Far from the edge nothing changes
+        assert_eq!((row[0], row[79]), (0, 240));
+    }
+
+    #[test]
+    fn redacting_a_copy_matches() {
+        let noise = |x: i32, y: i32| ((x * 37 + y * 91) % 251) as u8;
+        let mut whole = image(40, 30, 0, noise);
+        redact((5, 7, 20, 15), RedactStyle::Blur).apply(&mut whole, (40, 30), 40 * 4);
+        let mut copy = image(20, 15, 0, |x, y| noise(x + 5, y + 7));
+        redact((0, 0, 20, 15), RedactStyle::Blur).apply(&mut copy, (20, 15), 20 * 4);
+        for y in 0..15 {
+            for x in 0..20 {
+                assert_eq!(
+                    pixel(&copy, 20 * 4, x, y),
+                    pixel(&whole, 40 * 4, x + 5, y + 7)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn styles_cycle() {
+        assert_eq!(RedactStyle::Blur.next(), Some(RedactStyle::Pixelate));
+        assert_eq!(RedactStyle::Pixelate.next(), Some(RedactStyle::Fill));
+        assert_eq!(RedactStyle::Fill.next(), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Named region presets defined in the config file
+
+use crate::screenshot::Output;
+use crate::selection::Rect;
+
+/// A length that is either absolute or relative to the area it lives in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(f32),
+    Percent(f32),
+}
+
+impl Length {
+    /// Parse "800" or "50%"
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let length = match s.strip_suffix('%') {
+            Some(percent) => Length::Percent(percent.trim().parse().ok()?),
+            None => Length::Pixels(s.parse().ok()?),
+        };
+        match length {
+            Length::Pixels(v) | Length::Percent(v) if v > 0.0 => Some(length),
+            _ => None,
+        }
+    }
+
+    fn resolve(&self, total: f32) -> f32 {
+        match *self {
+            Length::Pixels(v) => v.min(total),
+            Length::Percent(v) => total * v.min(100.0) / 100.0,
+        }
+    }
+}
+
+/// How a named region is placed on screen
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionSpec {
+    /// A fixed rect in screen coordinates
+    Absolute(Rect),
+    /// A rect centered on the screen, or on an output
+    Center {
+        width: Length,
+        height: Length,
+        output: Option<String>,
+    },
+    /// The full area of an output
+    Output(String),
+}
+
+impl RegionSpec {
+    /// Parse a region spec
+    ///
+    /// * `"0,40 1920x1000"` or `"0,40 1920,1040"` — a fixed rect
+    /// * `"center 50% x 50%"` or `"center 800x600 on DP-1"` — a centered rect
+    /// * `"output DP-1"` — a whole output
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        if let Some(name) = s.strip_prefix("output ") {
+            let name = name.trim();
+            return (!name.is_empty()).then(|| RegionSpec::Output(name.to_string()));
+        }
+
+        if let Some(rest) = s.strip_prefix("center ") {
+            let (size, output) = match rest.split_once(" on ") {
+                Some((size, output)) => (size, Some(output.trim().to_string())),
+                None => (rest, None),
+            };
+            let size: String = size.chars().filter(|c| !c.is_whitespace()).collect();
+            let (width, height) = size.split_once('x')?;
+            return Some(RegionSpec::Center {
+                width: Length::parse(width)?,
+                height: Length::parse(height)?,
+                output,
+            });
+        }
+
+        Rect::parse(s).map(RegionSpec::Absolute)
+    }
+
+    /// Place the region on the current output layout, clamped to the
+    /// screen. Returns None if it refers to an output that isn't connected
+    /// or lies entirely off screen.
+    pub fn resolve(
+        &self,
+        outputs: &[Output],
+        screen_width: f32,
+        screen_height: f32,
+    ) -> Option<Rect> {
+        let find_output = |name: &str| outputs.iter().find(|o| o.name == name).map(|o| o.rect);
+
+        match self {
+            RegionSpec::Absolute(rect) => {
+                let rect = rect.normalized();
+                let (x, y) = (rect.x.max(0.0), rect.y.max(0.0));
+                let right = rect.right().min(screen_width);
+                let bottom = rect.bottom().min(screen_height);
+                (right > x && bottom > y).then(|| Rect::new(x, y, right - x, bottom - y))
+            }
+            RegionSpec::Output(name) => find_output(name),
+            RegionSpec::Center {
+                width,
+                height,
+                output,
+            } => {
+                let area = match output {
+                    Some(name) => find_output(name)?,
+                    None => Rect::new(0.0, 0.0, screen_width, screen_height),
+                };
+                let w = width.resolve(area.width);
+                let h = height.resolve(area.height);
+                Some(Rect::new(
+                    area.x + (area.width - w) / 2.0,
+                    area.y + (area.height - h) / 2.0,
+                    w,
+                    h,
+                ))
+            }
+        }
+    }
+}
+
+/// A region preset with the name it is invoked by
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedRegion {
+    pub name: String,
+    pub spec: RegionSpec,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs() -> Vec<Output> {
+        vec![
+            Output {
+                name: "DP-1".to_string(),
+                rect: Rect::new(0.0, 0.0, 1920.0, 1080.0),
+            },
+            Output {
+                name: "HDMI-A-1".to_string(),
+                rect: Rect::new(1920.0, 0.0, 1280.0, 1024.0),
+            },
+        ]
+    }
+
+    fn resolve(spec: &str) -> Option<Rect> {
+        RegionSpec::parse(spec)
+            .unwrap()
+            .resolve(&outputs(), 3200.0, 1080.0)
+    }
+
+    #[test]
+    fn parse_specs() {
+        assert_eq!(
+            RegionSpec::parse("center 50% x 800"),
+            Some(RegionSpec::Center {
+                width: Length::Percent(50.0),
+                height: Length::Pixels(800.0),
+                output: None,
+            })
+        );
+        assert_eq!(
+            RegionSpec::parse("output DP-1"),
+            Some(RegionSpec::Output("DP-1".to_string()))
+        );
+        for invalid in [
+            "output ",
+            "center 50%",
+            "center 0x600",
+            "center -5%x10%",
+            "left half",
+        ] {
+            assert_eq!(RegionSpec::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resolve_absolute() {
+        assert_eq!(
+            resolve("0,40 1920x1000"),
+            Some(Rect::new(0.0, 40.0, 1920.0, 1000.0))
+        );
+        // Clamped to the screen
+        assert_eq!(
+            resolve("3000,-100 400x400"),
+            Some(Rect::new(3000.0, 0.0, 200.0, 300.0))
+        );
+        assert_eq!(resolve("4000,0 100x100"), None);
+    }
+
+    #[test]
+    fn resolve_centered() {
+        assert_eq!(
+            resolve("center 50% x 50%"),
+            Some(Rect::new(800.0, 270.0, 1600.0, 540.0))
+        );
+        assert_eq!(
+            resolve("center 800x600 on HDMI-A-1"),
+            Some(Rect::new(2160.0, 212.0, 800.0, 600.0))
+        );
+        // Never larger than the output
+        assert_eq!(
+            resolve("center 200% x 5000 on HDMI-A-1"),
+            Some(Rect::new(1920.0, 0.0, 1280.0, 1024.0))
+        );
+        assert_eq!(resolve("center 800x600 on DP-2"), None);
+    }
+
+    #[test]
+    fn resolve_output() {
+        assert_eq!(
+            resolve("output HDMI-A-1"),
+            Some(Rect::new(1920.0, 0.0, 1280.0, 1024.0))
+        );
+        assert_eq!(resolve("output DP-2"), None);
+    }
+}
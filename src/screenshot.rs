@@ -1,6 +1,11 @@
 //! Screenshot capture functionality using libwayshot (wlr-screencopy protocol)
 
-use gdk_pixbuf::{Colorspace, Pixbuf};
+use crate::mask::Mask;
+use crate::redact::Redaction;
+use crate::selection::Rect;
+use gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use gtk4::cairo;
+use gtk4::prelude::*;
 use libwayshot::WayshotConnection;
 
 /// Error type for screenshot operations
@@ -23,7 +28,17 @@ impl std::fmt::Display for ScreenshotError {
 
 impl std::error::Error for ScreenshotError {}
 
+/// A monitor and its area within the captured image
+#[derive(Debug, Clone)]
+pub struct Output {
+    /// Connector name, e.g. "DP-1"
+    pub name: String,
+    /// Area covered by the output in screenshot coordinates
+    pub rect: Rect,
+}
+
 /// Captured screenshot data
+#[derive(Clone)]
 pub struct Screenshot {
     /// Loaded pixbuf for display
     pub pixbuf: Pixbuf,
@@ -31,6 +46,8 @@ pub struct Screenshot {
     pub width: i32,
     /// Screen height
     pub height: i32,
+    /// Layout of the captured outputs
+    pub outputs: Vec<Output>,
 }
 
 impl Screenshot {
@@ -45,6 +62,27 @@ impl Screenshot {
             .screenshot_all(false)
             .map_err(|e| ScreenshotError::WayshotError(e.to_string()))?;
 
+        // The combined image starts at the top-left-most output
+        let regions: Vec<_> = wayshot
+            .get_all_outputs()
+            .iter()
+            .map(|o| (o.name.clone(), o.logical_region.inner))
+            .collect();
+        let min_x = regions.iter().map(|(_, r)| r.position.x).min().unwrap_or(0);
+        let min_y = regions.iter().map(|(_, r)| r.position.y).min().unwrap_or(0);
+        let outputs = regions
+            .into_iter()
+            .map(|(name, r)| Output {
+                name,
+                rect: Rect::new(
+                    (r.position.x - min_x) as f32,
+                    (r.position.y - min_y) as f32,
+                    r.size.width as f32,
+                    r.size.height as f32,
+                ),
+            })
+            .collect();
+
         // Convert DynamicImage to RGBA8
         let rgba_image = image.to_rgba8();
         let width = rgba_image.width() as i32;
@@ -66,17 +104,78 @@ impl Screenshot {
             pixbuf,
             width,
             height,
+            outputs,
         })
     }
 
-    /// Crop the screenshot to the given rectangle
-    pub fn crop(
+    /// A copy of the screenshot with the redactions burnt into its pixels,
+    /// so crops of it can't reveal what they covered
+    pub fn redacted(&self, redactions: &[Redaction]) -> Screenshot {
+        let stride = self.pixbuf.rowstride() as usize;
+        let mut pixels = self.pixbuf.read_pixel_bytes().to_vec();
+        for redaction in redactions {
+            redaction.apply(&mut pixels, (self.width, self.height), stride);
+        }
+
+        // The screenshot pixbuf is always RGBA, see `capture`
+        let pixbuf = Pixbuf::from_bytes(
+            &glib::Bytes::from_owned(pixels),
+            Colorspace::Rgb,
+            true,
+            8,
+            self.width,
+            self.height,
+            stride as i32,
+        );
+        Screenshot {
+            pixbuf,
+            width: self.width,
+            height: self.height,
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    /// Lay cropped areas out on one image, at the given positions, over a
+    /// solid color or a transparent background
+    pub fn compose(
+        parts: &[(Pixbuf, (i32, i32))],
+        (width, height): (i32, i32),
+        background: Option<[u8; 3]>,
+    ) -> Result<Pixbuf, ScreenshotError> {
+        let image = Pixbuf::new(Colorspace::Rgb, true, 8, width.max(1), height.max(1))
+            .ok_or_else(|| ScreenshotError::PixbufError("Out of memory".to_string()))?;
+        image.fill(match background {
+            Some([r, g, b]) => u32::from_be_bytes([r, g, b, 255]),
+            None => 0,
+        });
+        for (part, (x, y)) in parts {
+            part.composite(
+                &image,
+                *x,
+                *y,
+                part.width(),
+                part.height(),
+                *x as f64,
+                *y as f64,
+                1.0,
+                1.0,
+                InterpType::Nearest,
+                255,
+            );
+        }
+        Ok(image)
+    }
+
+    /// Crop the screenshot to the given rectangle, clamped to the image.
+    /// With a mask, pixels outside it are made transparent.
+    pub fn crop_pixbuf(
         &self,
         x: i32,
         y: i32,
         width: i32,
         height: i32,
-    ) -> Result<Vec<u8>, ScreenshotError> {
+        mask: Option<&Mask>,
+    ) -> Pixbuf {
         // Clamp to valid bounds
         let x = x.max(0).min(self.width - 1);
         let y = y.max(0).min(self.height - 1);
@@ -84,11 +183,103 @@ impl Screenshot {
         let height = height.min(self.height - y).max(1);
 
         // Create a new subpixbuf for the selection
-        let cropped = self.pixbuf.new_subpixbuf(x, y, width, height);
+        let source = self.pixbuf.new_subpixbuf(x, y, width, height);
+        let Some(mask) = mask else {
+            return source;
+        };
+
+        // The screenshot pixbuf is always RGBA, see `capture`
+        let bytes = source.read_pixel_bytes();
+        let rowstride = source.rowstride() as usize;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in 0..height {
+            let offset = row as usize * rowstride;
+            let line = &bytes[offset..offset + width as usize * 4];
+            for (col, pixel) in line.chunks_exact(4).enumerate() {
+                let alpha = if mask.contains(x + col as i32, y + row) {
+                    pixel[3]
+                } else {
+                    0
+                };
+                pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], alpha]);
+            }
+        }
+
+        Pixbuf::from_bytes(
+            &glib::Bytes::from_owned(pixels),
+            Colorspace::Rgb,
+            true,
+            8,
+            width,
+            height,
+            width * 4,
+        )
+    }
+}
 
-        // Save to PNG bytes
-        cropped
-            .save_to_bufferv("png", &[])
-            .map_err(|e: glib::Error| ScreenshotError::PixbufError(e.to_string()))
+/// Draw over an image with cairo, e.g. to flatten annotations into it
+pub fn flatten(
+    image: &Pixbuf,
+    draw: impl FnOnce(&cairo::Context),
+) -> Result<Pixbuf, ScreenshotError> {
+    draw_image((image.width(), image.height()), |cr| {
+        cr.set_source_pixbuf(image, 0.0, 0.0);
+        cr.paint()?;
+        draw(cr);
+        Ok(())
+    })
+}
+
+/// Draw a new transparent image with cairo
+pub fn draw_image(
+    (width, height): (i32, i32),
+    draw: impl FnOnce(&cairo::Context) -> Result<(), cairo::Error>,
+) -> Result<Pixbuf, ScreenshotError> {
+    let error = |e: cairo::Error| ScreenshotError::PixbufError(e.to_string());
+    let mut surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).map_err(error)?;
+    {
+        let cr = cairo::Context::new(&surface).map_err(error)?;
+        draw(&cr).map_err(error)?;
+    }
+
+    // Cairo stores premultiplied native-endian ARGB, pixbufs straight RGBA
+    let stride = surface.stride() as usize;
+    let data = surface
+        .data()
+        .map_err(|e| ScreenshotError::PixbufError(e.to_string()))?;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in 0..height as usize {
+        let line = &data[row * stride..row * stride + width as usize * 4];
+        for pixel in line.chunks_exact(4) {
+            let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            let alpha = argb >> 24;
+            let channel = |shift: u32| {
+                let value = (argb >> shift) & 0xff;
+                if alpha == 0 {
+                    0
+                } else {
+                    ((value * 255 + alpha / 2) / alpha).min(255) as u8
+                }
+            };
+            pixels.extend_from_slice(&[channel(16), channel(8), channel(0), alpha as u8]);
+        }
     }
+
+    Ok(Pixbuf::from_bytes(
+        &glib::Bytes::from_owned(pixels),
+        Colorspace::Rgb,
+        true,
+        8,
+        width,
+        height,
+        width * 4,
+    ))
+}
+
+/// Encode an image as PNG
+pub fn encode_png(pixbuf: &Pixbuf) -> Result<Vec<u8>, ScreenshotError> {
+    pixbuf
+        .save_to_bufferv("png", &[])
+        .map_err(|e: glib::Error| ScreenshotError::PixbufError(e.to_string()))
 }
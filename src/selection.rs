@@ -1,6 +1,9 @@
 //! Selection box logic for handling drag, resize, and move operations
 
+use crate::mask::Mask;
+use crate::snap::{EdgeMap, SNAP_DISTANCE};
 use std::io::{self, BufRead, IsTerminal};
+use std::rc::Rc;
 
 /// Size of resize handles in pixels
 pub const HANDLE_SIZE: f32 = 14.0;
@@ -11,6 +14,12 @@ pub const EDGE_GRAB_WIDTH: f32 = 8.0;
 /// Minimum selection size in pixels
 pub const MIN_SIZE: f32 = 20.0;
 
+/// Maximum number of undo steps kept
+pub const HISTORY_LIMIT: usize = 100;
+
+/// Minimum distance between points of a freehand lasso outline
+const LASSO_STEP: f32 = 2.0;
+
 /// Which handle or edge is being dragged
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResizeEdge {
@@ -40,6 +49,99 @@ impl ResizeEdge {
     }
 }
 
+/// Size constraint applied to the selection by a preset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeConstraint {
+    /// Keep width / height at this ratio
+    Ratio(f32),
+    /// Keep the selection at exactly this size
+    Fixed(f32, f32),
+}
+
+/// A named selection size preset such as "16:9" or "1280x720"
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizePreset {
+    pub label: String,
+    pub constraint: SizeConstraint,
+}
+
+impl SizePreset {
+    /// Parse a preset from "W:H" (aspect ratio) or "WxH" (fixed size)
+    /// Example: "16:9" or "1280x720"
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (constraint, a, b) = if let Some((a, b)) = s.split_once(':') {
+            let a: f32 = a.trim().parse().ok()?;
+            let b: f32 = b.trim().parse().ok()?;
+            (SizeConstraint::Ratio(a / b), a, b)
+        } else if let Some((a, b)) = s.split_once('x') {
+            let a: f32 = a.trim().parse().ok()?;
+            let b: f32 = b.trim().parse().ok()?;
+            (SizeConstraint::Fixed(a, b), a, b)
+        } else {
+            return None;
+        };
+
+        if a > 0.0 && b > 0.0 {
+            Some(Self {
+                label: s.to_string(),
+                constraint,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Modifier keys held while dragging
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DragModifiers {
+    /// Keep a square while creating, or the starting aspect ratio while resizing
+    pub keep_aspect: bool,
+    /// Grow the selection symmetrically around the drag start (or the
+    /// center of the selection when resizing)
+    pub from_center: bool,
+}
+
+/// Shape of the selection within its bounding rect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionShape {
+    #[default]
+    Rect,
+    /// An ellipse inscribed in the rect
+    Ellipse,
+    /// A polygon placed one vertex per click
+    Polygon,
+    /// A freehand outline drawn in a single drag
+    Lasso,
+}
+
+impl SelectionShape {
+    /// Whether the shape is defined by outline points rather than its rect
+    pub fn has_outline(self) -> bool {
+        matches!(self, SelectionShape::Polygon | SelectionShape::Lasso)
+    }
+}
+
+/// A selection as stored in the undo history
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelectionState {
+    pub rect: Option<Rect>,
+    pub outline: Vec<(f32, f32)>,
+    pub extra_rects: Vec<Rect>,
+}
+
+/// A line that a selection edge has snapped to, shown as a guide
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapGuide {
+    /// A vertical line at x = `pos`, or a horizontal one at y = `pos`
+    pub vertical: bool,
+    pub pos: f32,
+    /// Extent of the line along its direction
+    pub start: f32,
+    pub end: f32,
+}
+
 /// Current drag mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum DragMode {
@@ -52,10 +154,14 @@ pub enum DragMode {
     Moving,
     /// Resizing via a specific edge or corner
     Resizing(ResizeEdge),
+    /// Drawing a freehand lasso outline
+    Drawing,
+    /// Dragging a single polygon vertex
+    MovingVertex(usize),
 }
 
 /// A rectangle representing the selection area
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -76,6 +182,9 @@ impl Rect {
     /// Parse a rect from format: "x1,y1 x2,y2"
     /// where x1,y1 is the top-left corner and x2,y2 is the bottom-right corner
     /// Example: "100,200 900,800" creates a rect at (100,200) with size 800x600
+    ///
+    /// The slurp format "x,y WxH" is accepted as well
+    /// Example: "100,200 800x600" creates the same rect
     pub fn parse(s: &str) -> Option<Self> {
         let s = s.trim();
         let parts: Vec<&str> = s.split_whitespace().collect();
@@ -91,17 +200,21 @@ impl Rect {
         let x1: f32 = p1[0].parse().ok()?;
         let y1: f32 = p1[1].parse().ok()?;
 
-        // Parse "x2,y2"
-        let p2: Vec<&str> = parts[1].split(',').collect();
-        if p2.len() != 2 {
-            return None;
-        }
-        let x2: f32 = p2[0].parse().ok()?;
-        let y2: f32 = p2[1].parse().ok()?;
+        let (width, height) = if let Some((w, h)) = parts[1].split_once('x') {
+            // Parse "WxH"
+            (w.parse().ok()?, h.parse().ok()?)
+        } else {
+            // Parse "x2,y2"
+            let p2: Vec<&str> = parts[1].split(',').collect();
+            if p2.len() != 2 {
+                return None;
+            }
+            let x2: f32 = p2[0].parse().ok()?;
+            let y2: f32 = p2[1].parse().ok()?;
 
-        // Calculate width and height from corner coordinates
-        let width = x2 - x1;
-        let height = y2 - y1;
+            // Calculate width and height from corner coordinates
+            (x2 - x1, y2 - y1)
+        };
 
         if width > 0.0 && height > 0.0 {
             Some(Self {
@@ -180,6 +293,59 @@ impl Rect {
     }
 }
 
+impl std::fmt::Display for Rect {
+    /// Format as "x1,y1 x2,y2", the format read by `Rect::parse`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rect = self.normalized();
+        write!(
+            f,
+            "{},{} {},{}",
+            rect.x,
+            rect.y,
+            rect.right(),
+            rect.bottom()
+        )
+    }
+}
+
+/// A region offered for quick selection, such as a window or an output
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredefinedRegion {
+    pub rect: Rect,
+    /// Name shown for the region, e.g. a window title
+    pub label: Option<String>,
+    /// App id or class of a window, used for per-app settings
+    pub app: Option<String>,
+}
+
+impl PredefinedRegion {
+    pub fn labelled(rect: Rect, label: impl Into<String>) -> Self {
+        let label = label.into();
+        Self {
+            rect,
+            label: (!label.is_empty()).then_some(label),
+            app: None,
+        }
+    }
+
+    /// Set the app a window region belongs to
+    pub fn with_app(mut self, app: &str) -> Self {
+        self.app = (!app.is_empty()).then(|| app.to_string());
+        self
+    }
+
+    /// Parse a region in any format read by `Rect::parse`, optionally
+    /// followed by a label
+    /// Example: "100,200 800x600 Firefox"
+    pub fn parse(s: &str) -> Option<Self> {
+        let (corner, rest) = s.trim().split_once(char::is_whitespace)?;
+        let rest = rest.trim_start();
+        let (size, label) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let rect = Rect::parse(&format!("{} {}", corner, size))?;
+        Some(Self::labelled(rect, label.trim()))
+    }
+}
+
 /// Selection state management
 #[derive(Debug, Clone, Default)]
 pub struct Selection {
@@ -194,10 +360,49 @@ pub struct Selection {
     pub drag_start: (f32, f32),
     /// Original rect when drag started
     pub drag_start_rect: Option<Rect>,
-    /// Predefined regions from stdin for quick selection
-    pub predefined_regions: Vec<Rect>,
+    /// Predefined regions for quick selection: from a saved session, stdin,
+    /// the compositor over IPC or detected in the screenshot
+    pub predefined_regions: Vec<PredefinedRegion>,
     /// Index of currently hovered predefined region
     pub hovered_region: Option<usize>,
+    /// Predefined regions under the cursor, smallest first
+    pub hover_stack: Vec<usize>,
+    /// Position of the hovered region in `hover_stack`
+    pub hover_depth: usize,
+    /// Available size presets
+    pub size_presets: Vec<SizePreset>,
+    /// Index of the active size preset, if any
+    pub active_preset: Option<usize>,
+    /// Edge map of the screenshot used for magnetic snapping
+    pub edge_map: Option<Rc<EdgeMap>>,
+    /// Whether selection edges snap to strong edges in the screenshot and to
+    /// the edges of predefined regions and outputs
+    pub snap_to_edges: bool,
+    /// Output areas, whose borders selection edges snap to
+    pub output_regions: Vec<Rect>,
+    /// Snap lines engaged by the current drag
+    pub snap_guides: Vec<SnapGuide>,
+    /// Previous selection states, most recent last
+    pub undo_stack: Vec<SelectionState>,
+    /// Undone selection states, most recent last
+    pub redo_stack: Vec<SelectionState>,
+    /// Pixels covered by a non-rectangular selection. Only applies while
+    /// its bounds match the selection rect.
+    pub mask: Option<Rc<Mask>>,
+    /// Shape of the selection, and of new selections
+    pub shape: SelectionShape,
+    /// Outline of a polygon or lasso selection; the rect is its bounding box
+    pub outline: Vec<(f32, f32)>,
+    /// Original outline when drag started
+    pub drag_start_outline: Vec<(f32, f32)>,
+    /// Whether a polygon is still being placed vertex by vertex
+    pub polygon_open: bool,
+    /// Selection before the polygon being placed, for the undo history
+    pub polygon_start_state: Option<SelectionState>,
+    /// Further areas added with Ctrl+drag, exported together with `rect`
+    pub extra_rects: Vec<Rect>,
+    /// Original extra areas when drag started
+    pub drag_start_extra_rects: Vec<Rect>,
 }
 
 impl Selection {
@@ -211,6 +416,24 @@ impl Selection {
             drag_start_rect: None,
             predefined_regions: Vec::new(),
             hovered_region: None,
+            hover_stack: Vec::new(),
+            hover_depth: 0,
+            size_presets: Vec::new(),
+            active_preset: None,
+            edge_map: None,
+            snap_to_edges: false,
+            output_regions: Vec::new(),
+            snap_guides: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            mask: None,
+            shape: SelectionShape::default(),
+            outline: Vec::new(),
+            drag_start_outline: Vec::new(),
+            polygon_open: false,
+            polygon_start_state: None,
+            extra_rects: Vec::new(),
+            drag_start_extra_rects: Vec::new(),
         }
     }
 
@@ -218,7 +441,7 @@ impl Selection {
     pub fn with_predefined_regions(
         screen_width: f32,
         screen_height: f32,
-        predefined_regions: Vec<Rect>,
+        predefined_regions: Vec<PredefinedRegion>,
     ) -> Self {
         Self {
             rect: None,
@@ -229,34 +452,365 @@ impl Selection {
             drag_start_rect: None,
             predefined_regions,
             hovered_region: None,
+            hover_stack: Vec::new(),
+            hover_depth: 0,
+            size_presets: Vec::new(),
+            active_preset: None,
+            edge_map: None,
+            snap_to_edges: false,
+            output_regions: Vec::new(),
+            snap_guides: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            mask: None,
+            shape: SelectionShape::default(),
+            outline: Vec::new(),
+            drag_start_outline: Vec::new(),
+            polygon_open: false,
+            polygon_start_state: None,
+            extra_rects: Vec::new(),
+            drag_start_extra_rects: Vec::new(),
         }
     }
 
-    /// Find which predefined region (if any) contains the given point
+    /// Find all predefined regions that contain the given point, smallest
+    /// first, so a window is found before the output it is on
+    pub fn predefined_regions_at(&self, x: f32, y: f32) -> Vec<usize> {
+        let area = |i: usize| {
+            let rect = self.predefined_regions[i].rect;
+            (rect.width * rect.height).abs()
+        };
+        let mut stack: Vec<usize> = (0..self.predefined_regions.len())
+            .filter(|&i| self.predefined_regions[i].rect.contains(x, y))
+            .collect();
+        stack.sort_by(|&a, &b| area(a).total_cmp(&area(b)));
+        stack
+    }
+
+    /// Find which predefined region (if any) is picked at the given point:
+    /// the hovered one if it contains the point, otherwise the smallest one
     pub fn find_predefined_region_at(&self, x: f32, y: f32) -> Option<usize> {
-        for (i, region) in self.predefined_regions.iter().enumerate() {
-            if region.contains(x, y) {
-                return Some(i);
+        if let Some(index) = self.hovered_region {
+            if self
+                .predefined_regions
+                .get(index)
+                .is_some_and(|r| r.rect.contains(x, y))
+            {
+                return Some(index);
             }
         }
-        None
+        self.predefined_regions_at(x, y).first().copied()
+    }
+
+    /// Replace the predefined regions, forgetting the hovered one
+    pub fn set_predefined_regions(&mut self, regions: Vec<PredefinedRegion>) {
+        self.predefined_regions = regions;
+        self.hovered_region = None;
+        self.hover_stack.clear();
+        self.hover_depth = 0;
     }
 
-    /// Update hovered region based on cursor position
+    /// Update hovered region based on cursor position. The region picked by
+    /// cycling is kept while the cursor stays over the same stack.
     pub fn update_hovered_region(&mut self, x: f32, y: f32) {
-        self.hovered_region = self.find_predefined_region_at(x, y);
+        let stack = self.predefined_regions_at(x, y);
+        if stack != self.hover_stack {
+            self.hover_stack = stack;
+            self.hover_depth = 0;
+        }
+        self.hovered_region = self.hover_stack.get(self.hover_depth).copied();
+    }
+
+    /// Hover the next larger (or smaller) region under the cursor, wrapping
+    /// around. Returns false if there is nothing to cycle through.
+    pub fn cycle_hovered_region(&mut self, forward: bool) -> bool {
+        let count = self.hover_stack.len();
+        if count < 2 {
+            return false;
+        }
+        self.hover_depth = if forward {
+            (self.hover_depth + 1) % count
+        } else {
+            (self.hover_depth + count - 1) % count
+        };
+        self.hovered_region = Some(self.hover_stack[self.hover_depth]);
+        true
     }
 
     /// Select a predefined region by index
     pub fn select_predefined_region(&mut self, index: usize) -> bool {
         if let Some(region) = self.predefined_regions.get(index) {
-            self.rect = Some(*region);
+            self.set_rect(Some(region.rect));
             true
         } else {
             false
         }
     }
 
+    /// Get the constraint of the active size preset
+    pub fn active_constraint(&self) -> Option<SizeConstraint> {
+        let index = self.active_preset?;
+        self.size_presets.get(index).map(|p| p.constraint)
+    }
+
+    /// Activate a size preset by index (or clear it with None) and apply it
+    /// to the current selection. Returns false if the index is out of range.
+    pub fn set_active_preset(&mut self, index: Option<usize>) -> bool {
+        if let Some(i) = index {
+            if i >= self.size_presets.len() {
+                return false;
+            }
+        }
+        self.active_preset = index;
+
+        let Some(constraint) = self.active_constraint() else {
+            return true;
+        };
+
+        // Keep the current center, or use the screen center for a fixed size
+        // preset when nothing is selected yet
+        let (cx, cy, width) = match (self.rect, constraint) {
+            (Some(rect), _) => {
+                let rect = rect.normalized();
+                (
+                    rect.x + rect.width / 2.0,
+                    rect.y + rect.height / 2.0,
+                    rect.width,
+                )
+            }
+            (None, SizeConstraint::Fixed(..)) => {
+                (self.screen_width / 2.0, self.screen_height / 2.0, 0.0)
+            }
+            (None, SizeConstraint::Ratio(_)) => return true,
+        };
+
+        let (mut w, mut h) = match constraint {
+            SizeConstraint::Ratio(ratio) => (width, width / ratio),
+            SizeConstraint::Fixed(w, h) => (w, h),
+        };
+
+        // Shrink to fit the screen while keeping the ratio
+        let scale = (self.screen_width / w).min(self.screen_height / h).min(1.0);
+        w *= scale;
+        h *= scale;
+
+        let rect = Rect::new(cx - w / 2.0, cy - h / 2.0, w, h);
+        self.set_rect(Some(rect.constrain(self.screen_width, self.screen_height)));
+        true
+    }
+
+    /// Replace the selection, and any extra areas, recording the previous
+    /// one in the undo history. Polygons and lassos start out as the rect's
+    /// corners.
+    pub fn set_rect(&mut self, rect: Option<Rect>) {
+        self.set_state(SelectionState {
+            rect,
+            outline: Vec::new(),
+            extra_rects: Vec::new(),
+        });
+    }
+
+    /// Replace the selection rect, outline and extra areas as a single edit
+    fn set_state(&mut self, state: SelectionState) {
+        let previous = self.state();
+        self.restore(state);
+        self.record_history(previous);
+    }
+
+    fn state(&self) -> SelectionState {
+        SelectionState {
+            rect: self.rect,
+            outline: self.outline.clone(),
+            extra_rects: self.extra_rects.clone(),
+        }
+    }
+
+    fn restore(&mut self, state: SelectionState) {
+        self.rect = state.rect;
+        self.outline = state.outline;
+        self.extra_rects = state.extra_rects;
+        self.polygon_open = false;
+        if let (true, true, Some(rect)) =
+            (self.shape.has_outline(), self.outline.is_empty(), self.rect)
+        {
+            self.outline = rect_corners(rect);
+        }
+    }
+
+    /// Switch the selection shape. The current selection keeps its bounds.
+    pub fn set_shape(&mut self, shape: SelectionShape) {
+        if self.polygon_open {
+            self.close_polygon();
+        }
+        self.shape = shape;
+        match self.rect {
+            Some(rect) if shape.has_outline() && self.outline.is_empty() => {
+                self.outline = rect_corners(rect.normalized());
+            }
+            _ if !shape.has_outline() => self.outline.clear(),
+            _ => {}
+        }
+    }
+
+    /// Finish the polygon being placed. Returns false if there is none.
+    pub fn close_polygon(&mut self) -> bool {
+        if !self.polygon_open {
+            return false;
+        }
+        self.polygon_open = false;
+        if self.outline.len() < 3 {
+            self.outline.clear();
+            self.rect = None;
+        }
+        let previous = self.polygon_start_state.take().unwrap_or_default();
+        self.record_history(previous);
+        true
+    }
+
+    /// Remove the last vertex of the polygon being placed, cancelling it when
+    /// none are left. Returns false if no polygon is being placed.
+    pub fn remove_polygon_vertex(&mut self) -> bool {
+        if !self.polygon_open {
+            return false;
+        }
+        self.outline.pop();
+        if self.outline.is_empty() {
+            self.polygon_open = false;
+            let previous = self.polygon_start_state.take().unwrap_or_default();
+            self.restore(previous);
+        } else {
+            self.sync_outline_rect();
+        }
+        true
+    }
+
+    /// Make the rect the bounding box of the outline
+    fn sync_outline_rect(&mut self) {
+        let xs = self.outline.iter().map(|p| p.0);
+        let ys = self.outline.iter().map(|p| p.1);
+        let (Some(x0), Some(x1)) = (xs.clone().reduce(f32::min), xs.reduce(f32::max)) else {
+            self.rect = None;
+            return;
+        };
+        let y0 = ys.clone().fold(f32::INFINITY, f32::min);
+        let y1 = ys.fold(f32::NEG_INFINITY, f32::max);
+        self.rect = Some(Rect::new(x0, y0, x1 - x0, y1 - y0));
+    }
+
+    /// Find the polygon vertex under the given point
+    fn hit_test_vertex(&self, x: f32, y: f32) -> Option<usize> {
+        if self.shape != SelectionShape::Polygon {
+            return None;
+        }
+        self.outline.iter().position(|&(vx, vy)| {
+            (vx - x).abs() <= HANDLE_SIZE / 2.0 && (vy - y).abs() <= HANDLE_SIZE / 2.0
+        })
+    }
+
+    /// Add a vertex to the polygon being placed, or close it when the first
+    /// vertex is clicked
+    fn add_polygon_vertex(&mut self, x: f32, y: f32) {
+        if self.outline.len() >= 3 && self.hit_test_vertex(x, y) == Some(0) {
+            self.close_polygon();
+            self.drag_mode = DragMode::None;
+            return;
+        }
+        self.outline.push(self.clamp_point(x, y));
+        self.sync_outline_rect();
+        self.drag_mode = DragMode::MovingVertex(self.outline.len() - 1);
+    }
+
+    fn clamp_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x.clamp(0.0, self.screen_width),
+            y.clamp(0.0, self.screen_height),
+        )
+    }
+
+    /// Pixels covered by the selection if it isn't a plain rectangle
+    pub fn crop_mask(&self) -> Option<Rc<Mask>> {
+        let (x, y, width, height) = self.get_crop_region()?;
+        match self.shape {
+            SelectionShape::Rect => self.active_mask().cloned(),
+            SelectionShape::Ellipse => Some(Rc::new(Mask::ellipse(x, y, width, height))),
+            SelectionShape::Polygon | SelectionShape::Lasso => {
+                Some(Rc::new(Mask::polygon(x, y, width, height, &self.outline)))
+            }
+        }
+    }
+
+    /// Select the bounding box of a mask, keeping the masked shape. This
+    /// replaces any extra areas.
+    pub fn set_mask(&mut self, mask: Mask) {
+        let previous = self.state();
+        self.extra_rects.clear();
+        self.rect = Some(mask.bounds());
+        self.outline.clear();
+        self.polygon_open = false;
+        self.record_history(previous);
+        self.mask = Some(Rc::new(mask));
+    }
+
+    /// The mask of the selection, if it is non-rectangular. Editing the
+    /// selection rect turns it back into a plain rectangle.
+    pub fn active_mask(&self) -> Option<&Rc<Mask>> {
+        let rect = self.rect?.normalized();
+        self.mask.as_ref().filter(|m| m.bounds() == rect)
+    }
+
+    /// Move the selection by the given offset, keeping it on screen
+    pub fn nudge(&mut self, dx: f32, dy: f32) {
+        if let Some(rect) = self.rect {
+            let rect = rect.normalized();
+            let moved = Rect::new(rect.x + dx, rect.y + dy, rect.width, rect.height)
+                .constrain(self.screen_width, self.screen_height);
+            let (ox, oy) = (moved.x - rect.x, moved.y - rect.y);
+            let outline = self
+                .outline
+                .iter()
+                .map(|&(x, y)| (x + ox, y + oy))
+                .collect();
+            self.set_state(SelectionState {
+                rect: Some(moved),
+                outline,
+                extra_rects: self.extra_rects.clone(),
+            });
+        }
+    }
+
+    /// Push the state before an edit onto the undo stack, unless the edit
+    /// didn't change anything. A new edit discards the redo stack.
+    fn record_history(&mut self, previous: SelectionState) {
+        if previous == self.state() {
+            return;
+        }
+        self.undo_stack.push(previous);
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Go back to the previous selection. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(previous) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.redo_stack.push(self.state());
+        self.restore(previous);
+        true
+    }
+
+    /// Reapply the last undone selection. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(next) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.undo_stack.push(self.state());
+        self.restore(next);
+        true
+    }
+
     /// Get the 4 corner handle rectangles for the current selection
     pub fn get_corner_handles(&self) -> Option<[(ResizeEdge, Rect); 4]> {
         let rect = self.rect?.normalized();
@@ -326,6 +880,11 @@ impl Selection {
 
     /// Determine what drag mode should be used for a click at the given point
     pub fn hit_test(&self, x: f32, y: f32) -> DragMode {
+        // Polygon vertices sit on top of everything else
+        if let Some(index) = self.hit_test_vertex(x, y) {
+            return DragMode::MovingVertex(index);
+        }
+
         // First check corner handles (highest priority)
         if let Some(edge) = self.hit_test_corner(x, y) {
             return DragMode::Resizing(edge);
@@ -354,6 +913,13 @@ impl Selection {
             return "grabbing";
         }
 
+        if self.polygon_open {
+            return "crosshair";
+        }
+        if self.hit_test_vertex(x, y).is_some() {
+            return "pointer";
+        }
+
         // Check corners first
         if let Some(edge) = self.hit_test_corner(x, y) {
             return edge.cursor_name();
@@ -376,25 +942,91 @@ impl Selection {
 
     /// Start a drag operation
     pub fn start_drag(&mut self, x: f32, y: f32) {
-        self.drag_mode = self.hit_test(x, y);
         self.drag_start = (x, y);
         self.drag_start_rect = self.rect;
+        self.drag_start_outline = self.outline.clone();
+        self.drag_start_extra_rects = self.extra_rects.clone();
 
+        if self.polygon_open {
+            self.add_polygon_vertex(x, y);
+            return;
+        }
+
+        self.drag_mode = self.hit_test(x, y);
         if self.drag_mode == DragMode::Creating {
-            self.rect = Some(Rect::new(x, y, 0.0, 0.0));
+            match self.shape {
+                SelectionShape::Polygon => {
+                    self.polygon_start_state = Some(self.state());
+                    self.polygon_open = true;
+                    self.outline.clear();
+                    self.add_polygon_vertex(x, y);
+                }
+                SelectionShape::Lasso => {
+                    self.outline = vec![self.clamp_point(x, y)];
+                    self.sync_outline_rect();
+                    self.drag_mode = DragMode::Drawing;
+                }
+                SelectionShape::Rect | SelectionShape::Ellipse => {
+                    self.rect = Some(Rect::new(x, y, 0.0, 0.0));
+                }
+            }
+            // A new selection replaces the extra areas
+            self.extra_rects.clear();
         }
     }
 
+    /// Start a drag that adds another area, keeping the current selection
+    /// as an extra area. Only plain rectangles can be combined; otherwise
+    /// this is a normal drag.
+    pub fn start_drag_adding(&mut self, x: f32, y: f32) {
+        let current = self.rect.filter(|_| {
+            self.shape == SelectionShape::Rect && self.active_mask().is_none() && !self.polygon_open
+        });
+        let Some(current) = current else {
+            self.start_drag(x, y);
+            return;
+        };
+
+        self.drag_start = (x, y);
+        self.drag_start_rect = self.rect;
+        self.drag_start_outline = self.outline.clone();
+        self.drag_start_extra_rects = self.extra_rects.clone();
+
+        self.extra_rects.push(current.normalized());
+        self.rect = Some(Rect::new(x, y, 0.0, 0.0));
+        self.drag_mode = DragMode::Creating;
+    }
+
     /// Update drag operation
-    pub fn update_drag(&mut self, x: f32, y: f32) {
+    pub fn update_drag(&mut self, x: f32, y: f32, modifiers: DragModifiers) {
         let (sx, sy) = self.drag_start;
         let dx = x - sx;
         let dy = y - sy;
+        self.snap_guides.clear();
 
         match self.drag_mode {
             DragMode::None => {}
             DragMode::Creating => {
-                self.rect = Some(Rect::new(sx, sy, dx, dy));
+                // Pull both corners onto nearby edges (the start point is the
+                // center, not a corner, when drawing from the center)
+                let (sx, sy) = if modifiers.from_center {
+                    (sx, sy)
+                } else {
+                    (
+                        self.snap_x(sx, sy, y).unwrap_or(sx),
+                        self.snap_y(sy, sx, x).unwrap_or(sy),
+                    )
+                };
+                let dx = self.snap_x(x, sy, y).unwrap_or(x) - sx;
+                let dy = self.snap_y(y, sx, x).unwrap_or(y) - sy;
+
+                let constraint = self.active_constraint().or(if modifiers.keep_aspect {
+                    Some(SizeConstraint::Ratio(1.0))
+                } else {
+                    None
+                });
+                self.rect =
+                    Some(self.corner_drag_rect(sx, sy, dx, dy, constraint, modifiers.from_center));
             }
             DragMode::Moving => {
                 if let Some(start_rect) = self.drag_start_rect {
@@ -406,18 +1038,346 @@ impl Selection {
                     );
                     // Constrain to screen
                     new_rect = new_rect.constrain(self.screen_width, self.screen_height);
+                    new_rect = self.snap_moved_rect(new_rect);
                     self.rect = Some(new_rect);
                 }
             }
             DragMode::Resizing(edge) => {
                 if let Some(start_rect) = self.drag_start_rect {
-                    let rect = self.apply_resize(start_rect, edge, dx, dy);
+                    let (dx, dy) = self.snap_resize_delta(start_rect, edge, dx, dy);
+                    let constraint = self.active_constraint().or(if modifiers.keep_aspect {
+                        Some(SizeConstraint::Ratio(start_rect.width / start_rect.height))
+                    } else {
+                        None
+                    });
+                    let rect = match constraint {
+                        // Fixed size presets can't be resized
+                        Some(SizeConstraint::Fixed(..)) => start_rect,
+                        Some(SizeConstraint::Ratio(ratio)) => self.apply_constrained_resize(
+                            start_rect,
+                            edge,
+                            dx,
+                            dy,
+                            Some(ratio),
+                            modifiers.from_center,
+                        ),
+                        None if modifiers.from_center => {
+                            self.apply_constrained_resize(start_rect, edge, dx, dy, None, true)
+                        }
+                        None => self.apply_resize(start_rect, edge, dx, dy),
+                    };
                     self.rect = Some(rect);
                 }
             }
+            DragMode::Drawing => {
+                let point = self.clamp_point(x, y);
+                let far_enough = self
+                    .outline
+                    .last()
+                    .is_none_or(|&(lx, ly)| (point.0 - lx).hypot(point.1 - ly) >= LASSO_STEP);
+                if far_enough {
+                    self.outline.push(point);
+                    self.sync_outline_rect();
+                }
+            }
+            DragMode::MovingVertex(index) => {
+                let point = self.clamp_point(x, y);
+                if let Some(vertex) = self.outline.get_mut(index) {
+                    *vertex = point;
+                }
+                self.sync_outline_rect();
+            }
+        }
+
+        // Outlines follow their bounding box when it is moved or resized
+        if matches!(self.drag_mode, DragMode::Moving | DragMode::Resizing(_))
+            && self.shape.has_outline()
+        {
+            if let (Some(from), Some(to)) = (self.drag_start_rect, self.rect) {
+                self.outline = map_points(&self.drag_start_outline, from, to);
+            }
         }
     }
 
+    /// Edges of predefined regions and outputs that selection edges snap to,
+    /// as (position, start, end) lines. `vertical` picks the left and right
+    /// edges, otherwise the top and bottom ones.
+    fn snap_lines(&self, vertical: bool) -> Vec<(f32, f32, f32)> {
+        self.predefined_regions
+            .iter()
+            .map(|r| &r.rect)
+            .chain(self.output_regions.iter())
+            .flat_map(|r| {
+                let r = r.normalized();
+                if vertical {
+                    [(r.x, r.y, r.bottom()), (r.right(), r.y, r.bottom())]
+                } else {
+                    [(r.y, r.x, r.right()), (r.bottom(), r.x, r.right())]
+                }
+            })
+            .collect()
+    }
+
+    /// Find the closest snap line within snapping distance of `pos` that
+    /// overlaps the span `from..to`, falling back to edges in the screenshot
+    fn find_snap(&self, vertical: bool, pos: f32, from: f32, to: f32) -> Option<SnapGuide> {
+        if !self.snap_to_edges {
+            return None;
+        }
+        let (lo, hi) = (from.min(to), from.max(to));
+
+        let closest = self
+            .snap_lines(vertical)
+            .into_iter()
+            .filter(|&(line, start, end)| {
+                (line - pos).abs() <= SNAP_DISTANCE && start <= hi && end >= lo
+            })
+            .min_by(|a, b| (a.0 - pos).abs().total_cmp(&(b.0 - pos).abs()));
+
+        // The guide covers both the snap line and the selection edge
+        let (line, start, end) = match closest {
+            Some((line, start, end)) => (line, start.min(lo), end.max(hi)),
+            None => {
+                let edge_map = self.edge_map.as_ref()?;
+                let line = if vertical {
+                    edge_map.snap_x(pos, from, to)?
+                } else {
+                    edge_map.snap_y(pos, from, to)?
+                };
+                (line, lo, hi)
+            }
+        };
+
+        Some(SnapGuide {
+            vertical,
+            pos: line,
+            start,
+            end,
+        })
+    }
+
+    /// Snap a vertical selection edge at `x` spanning `y0..y1`, if snapping
+    /// is enabled and a snap target is close enough
+    fn snap_x(&mut self, x: f32, y0: f32, y1: f32) -> Option<f32> {
+        let guide = self.find_snap(true, x, y0, y1)?;
+        self.snap_guides.push(guide);
+        Some(guide.pos)
+    }
+
+    /// Snap a horizontal selection edge at `y` spanning `x0..x1`
+    fn snap_y(&mut self, y: f32, x0: f32, x1: f32) -> Option<f32> {
+        let guide = self.find_snap(false, y, x0, x1)?;
+        self.snap_guides.push(guide);
+        Some(guide.pos)
+    }
+
+    /// Shift a moved rect so its nearest snapping edges line up, preferring
+    /// the left and top edges over the right and bottom ones
+    fn snap_moved_rect(&mut self, rect: Rect) -> Rect {
+        let mut rect = rect;
+        if let Some(x) = self.snap_x(rect.x, rect.y, rect.bottom()) {
+            rect.x = x;
+        } else if let Some(right) = self.snap_x(rect.right(), rect.y, rect.bottom()) {
+            rect.x = right - rect.width;
+        }
+        if let Some(y) = self.snap_y(rect.y, rect.x, rect.right()) {
+            rect.y = y;
+        } else if let Some(bottom) = self.snap_y(rect.bottom(), rect.x, rect.right()) {
+            rect.y = bottom - rect.height;
+        }
+        rect.constrain(self.screen_width, self.screen_height)
+    }
+
+    /// Adjust a resize offset so the dragged edges land on snapping edges
+    fn snap_resize_delta(&mut self, start: Rect, edge: ResizeEdge, dx: f32, dy: f32) -> (f32, f32) {
+        let start = start.normalized();
+        let edge_x = match edge {
+            ResizeEdge::Left | ResizeEdge::TopLeft | ResizeEdge::BottomLeft => Some(start.x),
+            ResizeEdge::Right | ResizeEdge::TopRight | ResizeEdge::BottomRight => {
+                Some(start.right())
+            }
+            ResizeEdge::Top | ResizeEdge::Bottom => None,
+        };
+        let edge_y = match edge {
+            ResizeEdge::Top | ResizeEdge::TopLeft | ResizeEdge::TopRight => Some(start.y),
+            ResizeEdge::Bottom | ResizeEdge::BottomLeft | ResizeEdge::BottomRight => {
+                Some(start.bottom())
+            }
+            ResizeEdge::Left | ResizeEdge::Right => None,
+        };
+
+        let mut dx = dx;
+        let mut dy = dy;
+        if let Some(ex) = edge_x {
+            if let Some(snapped) = self.snap_x(ex + dx, start.y, start.bottom()) {
+                dx = snapped - ex;
+            }
+        }
+        if let Some(ey) = edge_y {
+            if let Some(snapped) = self.snap_y(ey + dy, start.x, start.right()) {
+                dy = snapped - ey;
+            }
+        }
+        (dx, dy)
+    }
+
+    /// Build a rect from an anchor point and a drag offset, applying an
+    /// optional size constraint. The anchor is a fixed corner, or the center
+    /// of the rect when `centered` is set (the offset is then a half extent).
+    /// The result never leaves the screen: a constrained rect is scaled down
+    /// towards the anchor instead of being clipped, so its ratio survives
+    /// hitting a screen edge.
+    fn corner_drag_rect(
+        &self,
+        ax: f32,
+        ay: f32,
+        dx: f32,
+        dy: f32,
+        constraint: Option<SizeConstraint>,
+        centered: bool,
+    ) -> Rect {
+        let span = if centered { 2.0 } else { 1.0 };
+        let (mut dx, mut dy) = match constraint {
+            None => (dx, dy),
+            Some(SizeConstraint::Fixed(w, h)) => ((w / span).copysign(dx), (h / span).copysign(dy)),
+            Some(SizeConstraint::Ratio(ratio)) => {
+                // Follow whichever axis the cursor has moved further along
+                if dx.abs() >= dy.abs() * ratio {
+                    (dx, (dx.abs() / ratio).copysign(dy))
+                } else {
+                    ((dy.abs() * ratio).copysign(dx), dy)
+                }
+            }
+        };
+
+        let room_x = axis_room(ax, dx, self.screen_width, centered);
+        let room_y = axis_room(ay, dy, self.screen_height, centered);
+
+        if constraint.is_some() {
+            let mut scale: f32 = 1.0;
+            if dx.abs() > room_x {
+                scale = scale.min(room_x / dx.abs());
+            }
+            if dy.abs() > room_y {
+                scale = scale.min(room_y / dy.abs());
+            }
+            dx *= scale;
+            dy *= scale;
+        } else {
+            dx = dx.clamp(-room_x, room_x);
+            dy = dy.clamp(-room_y, room_y);
+        }
+
+        if centered {
+            Rect::new(ax - dx, ay - dy, dx * 2.0, dy * 2.0)
+        } else {
+            Rect::new(ax, ay, dx, dy)
+        }
+    }
+
+    /// Apply a resize that keeps an aspect ratio and/or grows symmetrically
+    /// around the center. Corners keep the opposite corner (or the center)
+    /// fixed. Edges keep the opposite edge (or the center) fixed; with a
+    /// ratio the other axis grows symmetrically around its center.
+    fn apply_constrained_resize(
+        &self,
+        start: Rect,
+        edge: ResizeEdge,
+        dx: f32,
+        dy: f32,
+        ratio: Option<f32>,
+        from_center: bool,
+    ) -> Rect {
+        let start = start.normalized();
+        let cx = start.x + start.width / 2.0;
+        let cy = start.y + start.height / 2.0;
+        let span = if from_center { 2.0 } else { 1.0 };
+
+        let corner = match edge {
+            ResizeEdge::TopLeft => Some((start.x, start.y)),
+            ResizeEdge::TopRight => Some((start.right(), start.y)),
+            ResizeEdge::BottomRight => Some((start.right(), start.bottom())),
+            ResizeEdge::BottomLeft => Some((start.x, start.bottom())),
+            _ => None,
+        };
+
+        let rect = if let Some((corner_x, corner_y)) = corner {
+            let (ax, ay) = if from_center {
+                (cx, cy)
+            } else {
+                (2.0 * cx - corner_x, 2.0 * cy - corner_y)
+            };
+            self.corner_drag_rect(
+                ax,
+                ay,
+                corner_x + dx - ax,
+                corner_y + dy - ay,
+                ratio.map(SizeConstraint::Ratio),
+                from_center,
+            )
+        } else if matches!(edge, ResizeEdge::Top | ResizeEdge::Bottom) {
+            let edge_y = if edge == ResizeEdge::Top {
+                start.y
+            } else {
+                start.bottom()
+            };
+            let anchor_y = if from_center { cy } else { 2.0 * cy - edge_y };
+            let mut extent = edge_y + dy - anchor_y;
+
+            let mut max_extent = axis_room(anchor_y, extent, self.screen_height, from_center);
+            if let Some(ratio) = ratio {
+                let room_x = cx.min(self.screen_width - cx) * 2.0;
+                max_extent = max_extent.min(room_x / ratio / span);
+            }
+            extent = extent.clamp(-max_extent, max_extent);
+
+            let height = extent * span;
+            let y = if from_center {
+                anchor_y - extent
+            } else {
+                anchor_y
+            };
+            match ratio {
+                Some(ratio) => {
+                    let width = height.abs() * ratio;
+                    Rect::new(cx - width / 2.0, y, width, height)
+                }
+                None => Rect::new(start.x, y, start.width, height),
+            }
+        } else {
+            let edge_x = if edge == ResizeEdge::Left {
+                start.x
+            } else {
+                start.right()
+            };
+            let anchor_x = if from_center { cx } else { 2.0 * cx - edge_x };
+            let mut extent = edge_x + dx - anchor_x;
+
+            let mut max_extent = axis_room(anchor_x, extent, self.screen_width, from_center);
+            if let Some(ratio) = ratio {
+                let room_y = cy.min(self.screen_height - cy) * 2.0;
+                max_extent = max_extent.min(room_y * ratio / span);
+            }
+            extent = extent.clamp(-max_extent, max_extent);
+
+            let width = extent * span;
+            let x = if from_center {
+                anchor_x - extent
+            } else {
+                anchor_x
+            };
+            match ratio {
+                Some(ratio) => {
+                    let height = width.abs() / ratio;
+                    Rect::new(x, cy - height / 2.0, width, height)
+                }
+                None => Rect::new(x, start.y, width, start.height),
+            }
+        };
+
+        rect.normalized()
+    }
+
     /// Apply resize operation based on edge
     fn apply_resize(&self, start: Rect, edge: ResizeEdge, dx: f32, dy: f32) -> Rect {
         let mut rect = start;
@@ -466,24 +1426,51 @@ impl Selection {
 
     /// End drag operation
     pub fn end_drag(&mut self) {
-        if let Some(ref mut rect) = self.rect {
+        if self.shape.has_outline() {
+            // Too short to enclose anything
+            if self.drag_mode == DragMode::Drawing && self.outline.len() < 3 {
+                self.outline.clear();
+            }
+            self.sync_outline_rect();
+        } else if let Some(ref mut rect) = self.rect {
             *rect = rect
                 .normalized()
                 .constrain(self.screen_width, self.screen_height);
         }
+        // A polygon is recorded once it is closed
+        if self.drag_mode != DragMode::None && !self.polygon_open {
+            let previous = SelectionState {
+                rect: self.drag_start_rect,
+                outline: std::mem::take(&mut self.drag_start_outline),
+                extra_rects: std::mem::take(&mut self.drag_start_extra_rects),
+            };
+            self.record_history(previous);
+        }
         self.drag_mode = DragMode::None;
         self.drag_start_rect = None;
+        self.snap_guides.clear();
     }
 
     /// Get the current selection as integer values for cropping
     pub fn get_crop_region(&self) -> Option<(i32, i32, i32, i32)> {
-        let rect = self.rect?.normalized();
-        Some((
-            rect.x.round() as i32,
-            rect.y.round() as i32,
-            rect.width.round() as i32,
-            rect.height.round() as i32,
-        ))
+        self.rect.map(crop_region)
+    }
+
+    /// Get all selected areas for cropping: the extra areas in the order
+    /// they were added, then the current selection
+    pub fn get_crop_regions(&self) -> Vec<(i32, i32, i32, i32)> {
+        self.selected_areas().into_iter().map(crop_region).collect()
+    }
+
+    /// All selected areas, normalized: the extra areas, then the current
+    /// selection
+    pub fn selected_areas(&self) -> Vec<Rect> {
+        self.extra_rects
+            .iter()
+            .copied()
+            .chain(self.rect)
+            .map(|rect| rect.normalized())
+            .collect()
     }
 
     /// Check if there's a valid selection
@@ -497,10 +1484,55 @@ impl Selection {
     }
 }
 
+/// Round a rect to integer values for cropping
+fn crop_region(rect: Rect) -> (i32, i32, i32, i32) {
+    let rect = rect.normalized();
+    (
+        rect.x.round() as i32,
+        rect.y.round() as i32,
+        rect.width.round() as i32,
+        rect.height.round() as i32,
+    )
+}
+
+/// Room available along one axis for a drag offset from `anchor`. A
+/// centered drag grows both ways, so it is limited by the nearer edge.
+fn axis_room(anchor: f32, offset: f32, screen_size: f32, centered: bool) -> f32 {
+    let room = if centered {
+        anchor.min(screen_size - anchor)
+    } else if offset < 0.0 {
+        anchor
+    } else {
+        screen_size - anchor
+    };
+    room.max(0.0)
+}
+
+/// Corners of a rect, clockwise from the top left
+fn rect_corners(rect: Rect) -> Vec<(f32, f32)> {
+    let rect = rect.normalized();
+    vec![
+        (rect.x, rect.y),
+        (rect.right(), rect.y),
+        (rect.right(), rect.bottom()),
+        (rect.x, rect.bottom()),
+    ]
+}
+
+/// Map points from one rect onto another, scaling them with it
+fn map_points(points: &[(f32, f32)], from: Rect, to: Rect) -> Vec<(f32, f32)> {
+    let scale = |to: f32, from: f32| if from != 0.0 { to / from } else { 1.0 };
+    let (sx, sy) = (scale(to.width, from.width), scale(to.height, from.height));
+    points
+        .iter()
+        .map(|&(x, y)| (to.x + (x - from.x) * sx, to.y + (y - from.y) * sy))
+        .collect()
+}
+
 /// Read predefined regions from stdin if stdin is not a terminal.
-/// Format: one region per line, in slurp format "x,y WxH"
-/// Example: "100,200 800x600"
-pub fn read_predefined_regions_from_stdin() -> Vec<Rect> {
+/// Format: one region per line, in slurp format "x,y WxH" with an optional label
+/// Example: "100,200 800x600 Firefox"
+pub fn read_predefined_regions_from_stdin() -> Vec<PredefinedRegion> {
     let stdin = io::stdin();
 
     // Only read if stdin is not a terminal (i.e., piped input)
@@ -511,10 +1543,61 @@ pub fn read_predefined_regions_from_stdin() -> Vec<Rect> {
     let mut regions = Vec::new();
     for line in stdin.lock().lines() {
         if let Ok(line) = line {
-            if let Some(rect) = Rect::parse(&line) {
-                regions.push(rect);
+            if let Some(region) = PredefinedRegion::parse(&line) {
+                regions.push(region);
             }
         }
     }
     regions
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hovered_region_outlives_regions() {
+        let regions = vec![
+            PredefinedRegion::labelled(Rect::new(0.0, 0.0, 1920.0, 1080.0), "output"),
+            PredefinedRegion::labelled(Rect::new(100.0, 100.0, 200.0, 200.0), "window"),
+        ];
+        let mut selection = Selection::with_predefined_regions(1920.0, 1080.0, regions);
+        selection.update_hovered_region(150.0, 150.0);
+        assert_eq!(selection.hovered_region, Some(1));
+
+        // A stale index is ignored rather than indexed
+        selection.predefined_regions.truncate(1);
+        assert_eq!(selection.find_predefined_region_at(150.0, 150.0), Some(0));
+
+        selection.set_predefined_regions(Vec::new());
+        assert_eq!(selection.hovered_region, None);
+        assert_eq!(selection.find_predefined_region_at(150.0, 150.0), None);
+    }
+
+    #[test]
+    fn new_selection_replaces_extra_areas() {
+        let mut selection = Selection::new(1920.0, 1080.0);
+        selection.set_rect(Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+        selection.start_drag_adding(500.0, 500.0);
+        selection.update_drag(600.0, 600.0, DragModifiers::default());
+        selection.end_drag();
+        assert_eq!(selection.selected_areas().len(), 2);
+
+        // Moving the current area keeps the others
+        selection.nudge(10.0, 0.0);
+        assert_eq!(
+            selection.selected_areas(),
+            [
+                Rect::new(0.0, 0.0, 100.0, 100.0),
+                Rect::new(510.0, 500.0, 100.0, 100.0)
+            ]
+        );
+
+        let region = Rect::new(200.0, 200.0, 50.0, 50.0);
+        selection.set_rect(Some(region));
+        assert_eq!(selection.selected_areas(), [region]);
+
+        assert!(selection.undo());
+        assert_eq!(selection.selected_areas().len(), 2);
+    }
+}
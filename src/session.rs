@@ -0,0 +1,246 @@
+//! Overlay sessions saved as `.waysnip` projects, to change the selection
+//! long after the screen has changed
+//!
+//! A project is a PNG of the whole frozen capture, so image viewers can open
+//! it too, with the selection, predefined regions and output layout in a
+//! text chunk.
+
+use crate::screenshot::Screenshot;
+use crate::selection::{PredefinedRegion, Rect};
+use crate::state::{outputs_to_toml, parse_outputs, parse_rect};
+use gdk_pixbuf::Pixbuf;
+use std::path::Path;
+
+/// File extension of session projects
+pub const EXTENSION: &str = "waysnip";
+
+/// PNG text chunk holding the session as TOML
+const TEXT_KEY: &str = "tEXt::waysnip";
+
+/// Error type for session operations
+#[derive(Debug)]
+pub enum SessionError {
+    ReadError(String),
+    WriteError(String),
+    /// An image without a session in it
+    NotASession,
+    ParseError(String),
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::ReadError(msg) => write!(f, "Failed to read session: {}", msg),
+            SessionError::WriteError(msg) => write!(f, "Failed to write session: {}", msg),
+            SessionError::NotASession => write!(f, "Not a waysnip session"),
+            SessionError::ParseError(msg) => write!(f, "Invalid session file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// A frozen capture and the overlay state to reopen it with
+pub struct Session {
+    /// The capture and the output layout it was taken on
+    pub screenshot: Screenshot,
+    pub selection: Option<Rect>,
+    pub regions: Vec<PredefinedRegion>,
+}
+
+impl Session {
+    /// Load a project written by `save`
+    pub fn load(path: &Path) -> Result<Self, SessionError> {
+        let pixbuf = Pixbuf::from_file(path).map_err(|e| SessionError::ReadError(e.to_string()))?;
+        let contents = pixbuf.option(TEXT_KEY).ok_or(SessionError::NotASession)?;
+        // Screenshot pixbufs are always RGBA, see `Screenshot::capture`
+        if !pixbuf.has_alpha() || pixbuf.n_channels() != 4 {
+            return Err(SessionError::ParseError("capture isn't RGBA".to_string()));
+        }
+        Self::parse(&contents, pixbuf)
+    }
+
+    /// Write the project, replacing the file if it exists
+    pub fn save(&self, path: &Path) -> Result<(), SessionError> {
+        let toml = self.to_toml();
+        let data = self
+            .screenshot
+            .pixbuf
+            .save_to_bufferv("png", &[(TEXT_KEY, toml.as_str())])
+            .map_err(|e| SessionError::WriteError(e.to_string()))?;
+        std::fs::write(path, data).map_err(|e| SessionError::WriteError(e.to_string()))
+    }
+
+    /// Parse the session stored with a capture
+    ///
+    /// ```toml
+    /// selection = "100,200 900,800"
+    ///
+    /// [[regions]]
+    /// rect = "0,0 1280,1024"
+    /// label = "Mozilla Firefox"
+    /// app = "firefox"
+    ///
+    /// [[outputs]]
+    /// name = "DP-1"
+    /// rect = "0,0 1920,1080"
+    /// ```
+    fn parse(contents: &str, pixbuf: Pixbuf) -> Result<Self, SessionError> {
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| SessionError::ParseError(e.to_string()))?;
+
+        let selection = match table.get("selection") {
+            Some(value) => Some(parse_rect(Some(value)).map_err(SessionError::ParseError)?),
+            None => None,
+        };
+        let regions = match table.get("regions").and_then(|v| v.as_array()) {
+            Some(regions) => regions
+                .iter()
+                .map(|r| {
+                    let rect = parse_rect(r.get("rect")).map_err(SessionError::ParseError)?;
+                    let label = r.get("label").and_then(|v| v.as_str()).unwrap_or("");
+                    let app = r.get("app").and_then(|v| v.as_str()).unwrap_or("");
+                    Ok(PredefinedRegion::labelled(rect, label).with_app(app))
+                })
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let outputs = parse_outputs(table.get("outputs")).map_err(SessionError::ParseError)?;
+
+        Ok(Self {
+            screenshot: Screenshot {
+                width: pixbuf.width(),
+                height: pixbuf.height(),
+                pixbuf,
+                outputs,
+            },
+            selection,
+            regions,
+        })
+    }
+
+    fn to_toml(&self) -> String {
+        let regions = self
+            .regions
+            .iter()
+            .map(|r| {
+                let mut region = toml::Table::new();
+                region.insert("rect".to_string(), r.rect.to_string().into());
+                if let Some(ref label) = r.label {
+                    region.insert("label".to_string(), label.clone().into());
+                }
+                if let Some(ref app) = r.app {
+                    region.insert("app".to_string(), app.clone().into());
+                }
+                toml::Value::Table(region)
+            })
+            .collect::<Vec<_>>();
+
+        let mut table = toml::Table::new();
+        if let Some(selection) = self.selection {
+            table.insert("selection".to_string(), selection.to_string().into());
+        }
+        table.insert("regions".to_string(), regions.into());
+        table.insert(
+            "outputs".to_string(),
+            outputs_to_toml(&self.screenshot.outputs),
+        );
+        table.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gdk_pixbuf::Colorspace;
+
+    fn parse(contents: &str) -> Result<Session, SessionError> {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, true, 8, 16, 12).unwrap();
+        Session::parse(contents, pixbuf)
+    }
+
+    #[test]
+    fn parse_session() {
+        let session = parse(
+            r#"
+            selection = "100,200 900,800"
+
+            [[regions]]
+            rect = "0,0 1280,1024"
+            label = "Mozilla Firefox"
+            app = "firefox"
+
+            [[regions]]
+            rect = "1280,0 1920,1080"
+
+            [[outputs]]
+            name = "DP-1"
+            rect = "0,0 1920,1080"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            session.selection,
+            Some(Rect::new(100.0, 200.0, 800.0, 600.0))
+        );
+        assert_eq!(
+            session.regions,
+            vec![
+                PredefinedRegion::labelled(Rect::new(0.0, 0.0, 1280.0, 1024.0), "Mozilla Firefox")
+                    .with_app("firefox"),
+                PredefinedRegion::labelled(Rect::new(1280.0, 0.0, 640.0, 1080.0), ""),
+            ]
+        );
+        let outputs = &session.screenshot.outputs;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].name, "DP-1");
+        assert_eq!(outputs[0].rect, Rect::new(0.0, 0.0, 1920.0, 1080.0));
+        assert_eq!(
+            (session.screenshot.width, session.screenshot.height),
+            (16, 12)
+        );
+    }
+
+    #[test]
+    fn parse_empty_session() {
+        let session = parse("").unwrap();
+        assert_eq!(session.selection, None);
+        assert!(session.regions.is_empty());
+        assert!(session.screenshot.outputs.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_session() {
+        for contents in [
+            "selection = [",
+            "selection = \"everything\"",
+            "[[regions]]\nlabel = \"no rect\"",
+            "[[outputs]]\nrect = \"0,0 10,10\"",
+        ] {
+            assert!(
+                matches!(parse(contents), Err(SessionError::ParseError(_))),
+                "{}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let session = parse(
+            "selection = \"10,20 30,40\"\n\
+             [[regions]]\nrect = \"0,0 8,8\"\nlabel = \"Terminal\"\napp = \"foot\"\n\
+             [[outputs]]\nname = \"eDP-1\"\nrect = \"0,0 16,12\"",
+        )
+        .unwrap();
+        let reparsed = parse(&session.to_toml()).unwrap();
+        assert_eq!(reparsed.selection, session.selection);
+        assert_eq!(reparsed.regions, session.regions);
+        assert_eq!(reparsed.screenshot.outputs[0].name, "eDP-1");
+        assert_eq!(
+            reparsed.screenshot.outputs[0].rect,
+            session.screenshot.outputs[0].rect
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Finding the client-side drop shadow around a window, to crop windows with
+//! or without it whatever geometry the compositor reports
+//!
+//! GTK and libadwaita windows draw their own shadow, and compositors
+//! disagree on whether the window geometry includes it. The shadow shows up
+//! as a smooth darkening of the background that ends at a sharp window edge,
+//! so each side is scanned across its edge for that pattern.
+
+use crate::pixels::Pixels;
+
+/// Largest shadow margin looked for on each side, inside and outside the
+/// region
+const MAX_MARGIN: i32 = 64;
+
+/// Number of lines scanned across each side
+const LINES: i32 = 16;
+
+/// Smallest luminance step counted as the window edge
+const EDGE_STEP: u8 = 16;
+
+/// Largest luminance difference still counted as unshadowed background
+const FADE: u8 = 3;
+
+/// How windows given as predefined regions are cropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowCrop {
+    /// Use the geometry reported by the compositor
+    #[default]
+    Keep,
+    /// Window content only, without the shadow
+    Tight,
+    /// Window content and its shadow
+    WithShadow,
+}
+
+impl ShadowCrop {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "keep" => Some(ShadowCrop::Keep),
+            "tight" => Some(ShadowCrop::Tight),
+            "shadow" => Some(ShadowCrop::WithShadow),
+            _ => None,
+        }
+    }
+}
+
+/// The two ways to crop a window with a shadow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shadow {
+    /// Window content only
+    pub tight: (i32, i32, i32, i32),
+    /// Window content and its shadow
+    pub with_shadow: (i32, i32, i32, i32),
+}
+
+impl Shadow {
+    /// The crop for a mode, or None to keep the region as it is
+    pub fn crop(&self, mode: ShadowCrop) -> Option<(i32, i32, i32, i32)> {
+        match mode {
+            ShadowCrop::Keep => None,
+            ShadowCrop::Tight => Some(self.tight),
+            ShadowCrop::WithShadow => Some(self.with_shadow),
+        }
+    }
+}
+
+/// Find the shadow around the window nearest to a region, whether the region
+/// includes the shadow or not. Returns None if no side has a shadow.
+pub fn detect(pixels: &Pixels, (x, y, width, height): (i32, i32, i32, i32)) -> Option<Shadow> {
+    if width < 4 || height < 4 {
+        return None;
+    }
+
+    // Pixels at distance `t` from each edge, negative outside the region
+    let rows = (y, height);
+    let columns = (x, width);
+    let left = side(pixels, rows, width, |row, t| (x + t, row));
+    let right = side(pixels, rows, width, |row, t| (x + width - 1 - t, row));
+    let top = side(pixels, columns, height, |column, t| (column, y + t));
+    let bottom = side(pixels, columns, height, |column, t| {
+        (column, y + height - 1 - t)
+    });
+    if [left, right, top, bottom].iter().all(Option::is_none) {
+        return None;
+    }
+
+    // Offsets of the shadow and the window edge inward from each side
+    let [left, right, top, bottom] = [left, right, top, bottom].map(|s| s.unwrap_or((0, 0)));
+    let rect = |(l, r, t, b): (i32, i32, i32, i32)| (x + l, y + t, width - l - r, height - t - b);
+    let tight = rect((left.1, right.1, top.1, bottom.1));
+    let with_shadow = rect((left.0, right.0, top.0, bottom.0));
+    (tight.2 > 0 && tight.3 > 0 && tight != with_shadow).then_some(Shadow { tight, with_shadow })
+}
+
+/// Scan lines across one side of a region and return the median offsets of
+/// the shadow and the window edge. `(start, length)` is the side's extent,
+/// `size` the region's extent across it, and `at` maps a line and a
+/// distance from the edge to a pixel.
+fn side(
+    pixels: &Pixels,
+    (start, length): (i32, i32),
+    size: i32,
+    at: impl Fn(i32, i32) -> (i32, i32),
+) -> Option<(i32, i32)> {
+    let in_bounds =
+        |(px, py): (i32, i32)| (0..pixels.width).contains(&px) && (0..pixels.height).contains(&py);
+    let mut shadows = Vec::new();
+    let mut edges = Vec::new();
+    // Don't scan past the middle of the region
+    let inside = MAX_MARGIN.min(size / 2);
+
+    // Lines across the middle half, clear of rounded corners
+    let lines = LINES.min(length / 2).max(1);
+    for i in 0..lines {
+        let line = start + length / 4 + i * (length / 2) / lines;
+        let outside = (1..=MAX_MARGIN)
+            .take_while(|&t| in_bounds(at(line, -t)))
+            .count() as i32;
+        if outside == 0 || !in_bounds(at(line, inside - 1)) {
+            continue;
+        }
+        let samples: Vec<u8> = (-outside..inside)
+            .map(|t| {
+                let (px, py) = at(line, t);
+                pixels.luma(px, py)
+            })
+            .collect();
+        if let Some((shadow, edge)) = line_edges(&samples) {
+            shadows.push(shadow as i32 - outside);
+            edges.push(edge as i32 - outside);
+        }
+    }
+
+    // Most lines have to agree
+    if edges.len() * 2 < lines as usize {
+        return None;
+    }
+    Some((median(&mut shadows), median(&mut edges)))
+}
+
+/// Find where the shadow starts and where the window begins on a line of
+/// luminance samples going inward from the background. The shadow has to
+/// darken steadily up to a sharp edge.
+fn line_edges(samples: &[u8]) -> Option<(usize, usize)> {
+    let background = *samples.first()?;
+    let edge = (1..samples.len()).find(|&i| samples[i].abs_diff(samples[i - 1]) > EDGE_STEP)?;
+    let shadow = (0..edge).find(|&i| background.saturating_sub(samples[i]) > FADE)?;
+    let darkens = samples[shadow..edge]
+        .windows(2)
+        .all(|pair| pair[1] <= pair[0].saturating_add(FADE));
+    darkens.then_some((shadow, edge))
+}
+
+fn median(values: &mut [i32]) -> i32 {
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 80x60 window at (60, 50) with a 12 pixel shadow, on a 200x160
+    /// background
+    fn window(shadow: i32) -> Pixels {
+        Pixels::from_fn(200, 160, |x, y| {
+            let dx = (60 - x).max(x - 139).max(0);
+            let dy = (50 - y).max(y - 109).max(0);
+            let luma = match dx.max(dy) {
+                0 => 250,
+                d if d <= shadow => 200 - (shadow + 1 - d) * 5,
+                _ => 200,
+            };
+            [luma as u8; 3]
+        })
+    }
+
+    const TIGHT: (i32, i32, i32, i32) = (60, 50, 80, 60);
+    const WITH_SHADOW: (i32, i32, i32, i32) = (48, 38, 104, 84);
+
+    #[test]
+    fn finds_shadow_from_either_geometry() {
+        let pixels = window(12);
+        let expected = Some(Shadow {
+            tight: TIGHT,
+            with_shadow: WITH_SHADOW,
+        });
+        assert_eq!(detect(&pixels, TIGHT), expected);
+        assert_eq!(detect(&pixels, WITH_SHADOW), expected);
+    }
+
+    #[test]
+    fn no_shadow() {
+        let pixels = window(0);
+        assert_eq!(detect(&pixels, TIGHT), None);
+        assert_eq!(detect(&pixels, (60, 50, 3, 60)), None);
+    }
+
+    #[test]
+    fn crop_modes() {
+        let shadow = Shadow {
+            tight: TIGHT,
+            with_shadow: WITH_SHADOW,
+        };
+        assert_eq!(shadow.crop(ShadowCrop::Keep), None);
+        assert_eq!(shadow.crop(ShadowCrop::Tight), Some(TIGHT));
+        assert_eq!(shadow.crop(ShadowCrop::WithShadow), Some(WITH_SHADOW));
+        assert_eq!(ShadowCrop::parse(" shadow "), Some(ShadowCrop::WithShadow));
+        assert_eq!(ShadowCrop::parse("none"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Edge detection for magnetic snapping of selection edges
+
+/// Distance in pixels within which an edge pulls the selection
+pub const SNAP_DISTANCE: f32 = 8.0;
+
+/// Minimum luminance step between neighbouring pixels to count as an edge
+const EDGE_CONTRAST: u8 = 24;
+
+/// Fraction of the span that must cross an edge for it to snap
+const MIN_COVERAGE: f32 = 0.6;
+
+/// Shortest span that is checked; shorter spans are widened around their middle
+const MIN_SPAN: i32 = 32;
+
+/// Maximum number of samples taken along a span
+const MAX_SAMPLES: i32 = 64;
+
+/// Luminance plane of a screenshot used to find strong straight edges
+pub struct EdgeMap {
+    width: i32,
+    height: i32,
+    luma: Vec<u8>,
+}
+
+impl std::fmt::Debug for EdgeMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EdgeMap({}x{})", self.width, self.height)
+    }
+}
+
+impl EdgeMap {
+    /// Create an edge map from a row-major luminance plane
+    pub fn new(width: i32, height: i32, luma: Vec<u8>) -> Self {
+        debug_assert_eq!(luma.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            luma,
+        }
+    }
+
+    fn luma(&self, x: i32, y: i32) -> u8 {
+        self.luma[(y * self.width + x) as usize]
+    }
+
+    /// Find the strongest vertical edge within snapping distance of `x`,
+    /// checked along rows `y0..y1`. Returns the x coordinate of the
+    /// boundary between the two differing columns.
+    pub fn snap_x(&self, x: f32, y0: f32, y1: f32) -> Option<f32> {
+        let (from, to) = span(y0, y1, self.height)?;
+        self.find_edge(x, self.width, from, to, |boundary, along| {
+            self.luma(boundary - 1, along)
+                .abs_diff(self.luma(boundary, along))
+        })
+    }
+
+    /// Find the strongest horizontal edge within snapping distance of `y`,
+    /// checked along columns `x0..x1`
+    pub fn snap_y(&self, y: f32, x0: f32, x1: f32) -> Option<f32> {
+        let (from, to) = span(x0, x1, self.width)?;
+        self.find_edge(y, self.height, from, to, |boundary, along| {
+            self.luma(along, boundary - 1)
+                .abs_diff(self.luma(along, boundary))
+        })
+    }
+
+    /// Score every boundary near `pos` by how much of the span crosses a
+    /// strong luminance step, and pick the best one (closest on ties)
+    fn find_edge(
+        &self,
+        pos: f32,
+        size: i32,
+        from: i32,
+        to: i32,
+        step_at: impl Fn(i32, i32) -> u8,
+    ) -> Option<f32> {
+        let center = pos.round() as i32;
+        let reach = SNAP_DISTANCE as i32;
+        let stride = ((to - from) / MAX_SAMPLES).max(1);
+
+        let mut best: Option<(f32, i32)> = None;
+        for boundary in (center - reach).max(1)..=(center + reach).min(size - 1) {
+            let mut samples = 0;
+            let mut hits = 0;
+            let mut along = from;
+            while along < to {
+                samples += 1;
+                if step_at(boundary, along) >= EDGE_CONTRAST {
+                    hits += 1;
+                }
+                along += stride;
+            }
+
+            let coverage = hits as f32 / samples.max(1) as f32;
+            if coverage < MIN_COVERAGE {
+                continue;
+            }
+
+            let better = match best {
+                None => true,
+                Some((best_coverage, best_boundary)) => {
+                    coverage > best_coverage
+                        || (coverage == best_coverage
+                            && (boundary - center).abs() < (best_boundary - center).abs())
+                }
+            };
+            if better {
+                best = Some((coverage, boundary));
+            }
+        }
+
+        best.map(|(_, boundary)| boundary as f32)
+    }
+}
+
+/// Clamp a span to the image, widening very short spans around their middle
+fn span(a: f32, b: f32, size: i32) -> Option<(i32, i32)> {
+    let (mut from, mut to) = (a.min(b).round() as i32, a.max(b).round() as i32);
+    if to - from < MIN_SPAN {
+        let mid = (from + to) / 2;
+        from = mid - MIN_SPAN / 2;
+        to = mid + MIN_SPAN / 2;
+    }
+    let from = from.max(0);
+    let to = to.min(size);
+    (to > from).then_some((from, to))
+}
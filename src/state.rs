@@ -0,0 +1,270 @@
+//! Persistent state kept between runs in $XDG_STATE_HOME/waysnip
+
+use crate::screenshot::Output;
+use crate::selection::Rect;
+use std::path::PathBuf;
+
+/// Error type for state operations
+#[derive(Debug)]
+pub enum StateError {
+    NoStateDir,
+    ReadError(String),
+    WriteError(String),
+    ParseError(String),
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::NoStateDir => write!(f, "Could not determine state directory"),
+            StateError::ReadError(msg) => write!(f, "Failed to read state: {}", msg),
+            StateError::WriteError(msg) => write!(f, "Failed to write state: {}", msg),
+            StateError::ParseError(msg) => write!(f, "Invalid state file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Get the state directory
+/// Uses $XDG_STATE_HOME/waysnip, falling back to ~/.local/state/waysnip
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(state_home.join("waysnip"))
+}
+
+/// The last exported crop region and the output layout it was taken on
+#[derive(Debug, Clone)]
+pub struct LastRegion {
+    pub rect: Rect,
+    pub outputs: Vec<Output>,
+}
+
+impl LastRegion {
+    fn path() -> Option<PathBuf> {
+        Some(state_dir()?.join("last-region.toml"))
+    }
+
+    /// Load the last region, if one was saved
+    pub fn load() -> Result<Option<Self>, StateError> {
+        let path = Self::path().ok_or(StateError::NoStateDir)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents =
+            std::fs::read_to_string(&path).map_err(|e| StateError::ReadError(e.to_string()))?;
+        Self::parse(&contents).map(Some)
+    }
+
+    /// Save this region, replacing the previous one
+    pub fn save(&self) -> Result<(), StateError> {
+        let path = Self::path().ok_or(StateError::NoStateDir)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| StateError::WriteError(e.to_string()))?;
+        }
+        std::fs::write(&path, self.to_toml()).map_err(|e| StateError::WriteError(e.to_string()))
+    }
+
+    /// Parse the state file
+    ///
+    /// ```toml
+    /// region = "100,200 900,800"
+    ///
+    /// [[outputs]]
+    /// name = "DP-1"
+    /// rect = "0,0 1920,1080"
+    /// ```
+    fn parse(contents: &str) -> Result<Self, StateError> {
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| StateError::ParseError(e.to_string()))?;
+
+        let rect = parse_rect(table.get("region")).map_err(StateError::ParseError)?;
+        let outputs = parse_outputs(table.get("outputs")).map_err(StateError::ParseError)?;
+        Ok(Self { rect, outputs })
+    }
+
+    fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+        table.insert("region".to_string(), self.rect.to_string().into());
+        table.insert("outputs".to_string(), outputs_to_toml(&self.outputs));
+        table.to_string()
+    }
+
+    /// Map the region onto the current output layout. If the output it was
+    /// taken on has moved, the region moves with it. Returns None if that
+    /// output is gone or the region no longer fits on screen.
+    pub fn resolve(
+        &self,
+        outputs: &[Output],
+        screen_width: f32,
+        screen_height: f32,
+    ) -> Option<Rect> {
+        let center_x = self.rect.x + self.rect.width / 2.0;
+        let center_y = self.rect.y + self.rect.height / 2.0;
+
+        let rect = match self
+            .outputs
+            .iter()
+            .find(|o| o.rect.contains(center_x, center_y))
+        {
+            Some(saved) => {
+                let current = outputs.iter().find(|o| o.name == saved.name)?;
+                Rect::new(
+                    self.rect.x - saved.rect.x + current.rect.x,
+                    self.rect.y - saved.rect.y + current.rect.y,
+                    self.rect.width,
+                    self.rect.height,
+                )
+            }
+            // No layout was recorded, use the region as is
+            None => self.rect,
+        };
+
+        let fits = rect.x >= 0.0
+            && rect.y >= 0.0
+            && rect.right() <= screen_width
+            && rect.bottom() <= screen_height;
+        fits.then_some(rect)
+    }
+}
+
+/// Parse a rect stored as a string in the format written by `Rect`'s Display
+pub fn parse_rect(value: Option<&toml::Value>) -> Result<Rect, String> {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(Rect::parse)
+        .ok_or_else(|| "invalid rect".to_string())
+}
+
+/// Parse an output layout stored by `outputs_to_toml`, empty if missing
+pub fn parse_outputs(value: Option<&toml::Value>) -> Result<Vec<Output>, String> {
+    let Some(outputs) = value.and_then(|v| v.as_array()) else {
+        return Ok(Vec::new());
+    };
+    outputs
+        .iter()
+        .map(|o| {
+            let name = o
+                .get("name")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| "output without name".to_string())?;
+            Ok(Output {
+                name: name.to_string(),
+                rect: parse_rect(o.get("rect"))?,
+            })
+        })
+        .collect()
+}
+
+/// An output layout as an array of tables with a name and rect each
+pub fn outputs_to_toml(outputs: &[Output]) -> toml::Value {
+    outputs
+        .iter()
+        .map(|o| {
+            let mut output = toml::Table::new();
+            output.insert("name".to_string(), o.name.clone().into());
+            output.insert("rect".to_string(), o.rect.to_string().into());
+            toml::Value::Table(output)
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, x: f32, y: f32) -> Output {
+        Output {
+            name: name.to_string(),
+            rect: Rect::new(x, y, 1920.0, 1080.0),
+        }
+    }
+
+    fn last_region() -> LastRegion {
+        LastRegion {
+            rect: Rect::new(2020.0, 100.0, 800.0, 600.0),
+            outputs: vec![output("DP-1", 0.0, 0.0), output("HDMI-A-1", 1920.0, 0.0)],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let saved = last_region();
+        let parsed = LastRegion::parse(&saved.to_toml()).unwrap();
+        assert_eq!(parsed.rect, saved.rect);
+        let outputs: Vec<_> = parsed
+            .outputs
+            .iter()
+            .map(|o| (o.name.as_str(), o.rect))
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                ("DP-1", Rect::new(0.0, 0.0, 1920.0, 1080.0)),
+                ("HDMI-A-1", Rect::new(1920.0, 0.0, 1920.0, 1080.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(LastRegion::parse("region = ").is_err());
+        assert!(LastRegion::parse("").is_err());
+        assert!(LastRegion::parse("region = \"nowhere\"").is_err());
+        assert!(
+            LastRegion::parse("region = \"0,0 10,10\"\n[[outputs]]\nrect = \"0,0 10,10\"").is_err()
+        );
+
+        // Regions saved without a layout still load
+        let parsed = LastRegion::parse("region = \"0,0 10,10\"").unwrap();
+        assert_eq!(parsed.rect, Rect::new(0.0, 0.0, 10.0, 10.0));
+        assert!(parsed.outputs.is_empty());
+    }
+
+    #[test]
+    fn resolve_same_layout() {
+        let outputs = [output("DP-1", 0.0, 0.0), output("HDMI-A-1", 1920.0, 0.0)];
+        assert_eq!(
+            last_region().resolve(&outputs, 3840.0, 1080.0),
+            Some(Rect::new(2020.0, 100.0, 800.0, 600.0))
+        );
+    }
+
+    #[test]
+    fn resolve_moved_output() {
+        // HDMI-A-1 is now to the left of DP-1
+        let outputs = [output("HDMI-A-1", 0.0, 0.0), output("DP-1", 1920.0, 0.0)];
+        assert_eq!(
+            last_region().resolve(&outputs, 3840.0, 1080.0),
+            Some(Rect::new(100.0, 100.0, 800.0, 600.0))
+        );
+    }
+
+    #[test]
+    fn resolve_disconnected_output() {
+        let outputs = [output("DP-1", 0.0, 0.0)];
+        assert_eq!(last_region().resolve(&outputs, 1920.0, 1080.0), None);
+    }
+
+    #[test]
+    fn resolve_off_screen() {
+        // The output is still there but the screen is smaller
+        let outputs = [output("DP-1", 0.0, 0.0), output("HDMI-A-1", 1920.0, 0.0)];
+        assert_eq!(last_region().resolve(&outputs, 2560.0, 1080.0), None);
+
+        // Without a recorded layout the region is used as is
+        let region = LastRegion {
+            outputs: Vec::new(),
+            ..last_region()
+        };
+        assert_eq!(region.resolve(&outputs, 3840.0, 1080.0), Some(region.rect));
+        assert_eq!(region.resolve(&outputs, 1920.0, 1080.0), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Shrinking a selection to its content by removing uniform borders
+
+use crate::pixels::Pixels;
+
+/// Default maximum per-channel color difference for a border to count as
+/// uniform
+pub const DEFAULT_TOLERANCE: u8 = 16;
+
+/// Remove rows and columns of near-uniform color from the edges of a
+/// region. Each side is trimmed while its outermost line matches the color
+/// that side started with, so a box drawn loosely around a dialog shrinks
+/// to the dialog. Returns None if nothing is left or nothing changed.
+pub fn trim(
+    pixels: &Pixels,
+    (x, y, width, height): (i32, i32, i32, i32),
+    tolerance: u8,
+) -> Option<(i32, i32, i32, i32)> {
+    // Clamp to the image
+    let mut left = x.max(0);
+    let mut top = y.max(0);
+    let mut right = (x + width).min(pixels.width) - 1;
+    let mut bottom = (y + height).min(pixels.height) - 1;
+    if left > right || top > bottom {
+        return None;
+    }
+
+    let similar = |a: [u8; 3], b: [u8; 3]| (0..3).all(|c| a[c].abs_diff(b[c]) <= tolerance);
+    let row_matches = |row: i32, left: i32, right: i32, color: [u8; 3]| {
+        (left..=right).all(|col| similar(pixels.rgb(col, row), color))
+    };
+    let column_matches = |col: i32, top: i32, bottom: i32, color: [u8; 3]| {
+        (top..=bottom).all(|row| similar(pixels.rgb(col, row), color))
+    };
+
+    let top_color = pixels.rgb(left, top);
+    while top <= bottom && row_matches(top, left, right, top_color) {
+        top += 1;
+    }
+    if top > bottom {
+        return None;
+    }
+    let bottom_color = pixels.rgb(left, bottom);
+    while bottom > top && row_matches(bottom, left, right, bottom_color) {
+        bottom -= 1;
+    }
+    let left_color = pixels.rgb(left, top);
+    while left <= right && column_matches(left, top, bottom, left_color) {
+        left += 1;
+    }
+    if left > right {
+        return None;
+    }
+    let right_color = pixels.rgb(right, top);
+    while right > left && column_matches(right, top, bottom, right_color) {
+        right -= 1;
+    }
+
+    let trimmed = (left, top, right - left + 1, bottom - top + 1);
+    (trimmed != (x, y, width, height)).then_some(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const DARK: [u8; 3] = [40, 40, 40];
+
+    /// A dark 10x10 box at (10, 8) on a 40x30 background
+    fn dialog(background: impl Fn(i32, i32) -> [u8; 3]) -> Pixels {
+        Pixels::from_fn(40, 30, |x, y| {
+            if (10..20).contains(&x) && (8..18).contains(&y) {
+                DARK
+            } else {
+                background(x, y)
+            }
+        })
+    }
+
+    #[test]
+    fn trims_to_content() {
+        let pixels = dialog(|_, _| WHITE);
+        assert_eq!(trim(&pixels, (0, 0, 40, 30), 0), Some((10, 8, 10, 10)));
+        // Clamped to the image first
+        assert_eq!(trim(&pixels, (-5, -5, 60, 50), 0), Some((10, 8, 10, 10)));
+    }
+
+    #[test]
+    fn nothing_to_trim() {
+        let pixels = dialog(|_, _| WHITE);
+        assert_eq!(trim(&pixels, (10, 8, 10, 10), 0), None);
+        assert_eq!(trim(&pixels, (25, 20, 10, 5), 0), None);
+        assert_eq!(trim(&pixels, (50, 40, 10, 10), 0), None);
+    }
+
+    #[test]
+    fn tolerance() {
+        let pixels = dialog(|x, y| if (x + y) % 2 == 0 { WHITE } else { [247; 3] });
+        assert_eq!(trim(&pixels, (0, 0, 40, 30), 8), Some((10, 8, 10, 10)));
+        assert_eq!(trim(&pixels, (0, 0, 40, 30), 4), None);
+    }
+
+    #[test]
+    fn each_side_keeps_its_own_color() {
+        // A red band along the top is trimmed, but not the white below it
+        let pixels = dialog(|_, y| if y < 5 { [200, 0, 0] } else { WHITE });
+        assert_eq!(trim(&pixels, (0, 0, 40, 30), 0), Some((10, 5, 10, 13)));
+    }
+}
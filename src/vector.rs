@@ -0,0 +1,222 @@
+//! Export to SVG and PDF, and callouts added around an exported area
+//!
+//! The crop is embedded as an image while annotations and callouts are drawn
+//! with cairo on the document itself, so they stay sharp at any zoom.
+
+use crate::annotate::{Annotation, AnnotationShape};
+use crate::beautify::Style;
+use crate::screenshot::ScreenshotError;
+use crate::selection::Rect;
+use gdk_pixbuf::Pixbuf;
+use gtk4::prelude::*;
+use gtk4::{cairo, pango};
+use std::any::Any;
+
+/// Text size of captions in pixels
+const CAPTION_SIZE: f64 = 16.0;
+
+/// Space around the caption text
+const CAPTION_PADDING: f64 = 12.0;
+
+/// Color of the border around an export
+const BORDER_COLOR: [u8; 3] = [0x3d, 0x3d, 0x3d];
+
+/// Largest border width in pixels
+pub const MAX_BORDER: f32 = 32.0;
+
+/// File format of saved images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Png,
+    Svg,
+    /// A single page
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Png, ExportFormat::Svg, ExportFormat::Pdf];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "png" => Some(ExportFormat::Png),
+            "svg" => Some(ExportFormat::Svg),
+            "pdf" => Some(ExportFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    /// File extension, also used as its name in the export menu
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Vector additions to an export: highlight boxes over it, a border around
+/// it and a caption below it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callouts {
+    /// Boxes relative to the exported area
+    pub boxes: Vec<Rect>,
+    /// Color and stroke width of the boxes
+    pub color: [u8; 3],
+    pub width: f32,
+    pub caption: Option<String>,
+    /// Border width in pixels, 0 for none
+    pub border: f32,
+}
+
+impl Default for Callouts {
+    fn default() -> Self {
+        Self {
+            boxes: Vec::new(),
+            color: crate::annotate::DEFAULT_COLORS[0],
+            width: crate::annotate::DEFAULT_WIDTH,
+            caption: None,
+            border: 0.0,
+        }
+    }
+}
+
+impl Callouts {
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty() && self.caption.is_none() && self.border <= 0.0
+    }
+}
+
+/// Render an exported area, beautified with `style` if set, with the
+/// callouts around it. `draw` adds the annotations in the area's
+/// coordinates.
+pub fn render(
+    image: &Pixbuf,
+    format: ExportFormat,
+    callouts: &Callouts,
+    style: Option<&Style>,
+    draw: impl FnOnce(&cairo::Context),
+) -> Result<Vec<u8>, ScreenshotError> {
+    let error = |e: cairo::Error| ScreenshotError::PixbufError(e.to_string());
+    let image_size = (image.width() as f64, image.height() as f64);
+    let (width, height) = style.map_or(image_size, |s| s.size(image_size));
+    let border = callouts.border.clamp(0.0, MAX_BORDER) as f64;
+
+    // Lay the caption out on a scratch surface to size the page
+    let caption = match callouts.caption {
+        Some(ref text) => {
+            let scratch =
+                cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).map_err(error)?;
+            let layout =
+                caption_layout(&cairo::Context::new(&scratch).map_err(error)?, text, width);
+            Some((text, layout.pixel_size().1 as f64 + CAPTION_PADDING * 2.0))
+        }
+        None => None,
+    };
+    let page_width = width + border * 2.0;
+    let page_height = height + border * 2.0 + caption.map_or(0.0, |(_, h)| h);
+
+    let draw_page = |cr: &cairo::Context| {
+        if caption.is_some() {
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            cr.paint().ok();
+        }
+        if border > 0.0 {
+            let [r, g, b] = BORDER_COLOR;
+            cr.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+            cr.set_line_width(border);
+            cr.rectangle(border / 2.0, border / 2.0, width + border, height + border);
+            cr.stroke().ok();
+        }
+
+        let paint_image = |cr: &cairo::Context| {
+            cr.rectangle(0.0, 0.0, image_size.0, image_size.1);
+            cr.clip();
+            cr.set_source_pixbuf(image, 0.0, 0.0);
+            cr.paint().ok();
+            cr.save().ok();
+            draw(cr);
+            cr.restore().ok();
+            for rect in &callouts.boxes {
+                Annotation {
+                    shape: AnnotationShape::Rectangle(rect.normalized()),
+                    color: callouts.color,
+                    width: callouts.width,
+                }
+                .draw(cr);
+            }
+        };
+        cr.save().ok();
+        cr.translate(border, border);
+        match style {
+            Some(style) => style.draw(cr, image_size, paint_image),
+            None => paint_image(cr),
+        }
+        cr.restore().ok();
+
+        if let Some((text, caption_height)) = caption {
+            let layout = caption_layout(cr, text, width);
+            cr.set_source_rgb(0.13, 0.13, 0.13);
+            cr.move_to(
+                border + CAPTION_PADDING,
+                page_height - caption_height + CAPTION_PADDING,
+            );
+            pangocairo::functions::show_layout(cr, &layout);
+        }
+    };
+
+    match format {
+        ExportFormat::Png => {
+            let surface = cairo::ImageSurface::create(
+                cairo::Format::ARgb32,
+                page_width.ceil() as i32,
+                page_height.ceil() as i32,
+            )
+            .map_err(error)?;
+            draw_page(&cairo::Context::new(&surface).map_err(error)?);
+            let mut data = Vec::new();
+            surface
+                .write_to_png(&mut data)
+                .map_err(|e| ScreenshotError::PixbufError(e.to_string()))?;
+            Ok(data)
+        }
+        ExportFormat::Svg => {
+            let surface = cairo::SvgSurface::for_stream(page_width, page_height, Vec::<u8>::new())
+                .map_err(error)?;
+            surface.set_document_unit(cairo::SvgUnit::Px);
+            draw_page(&cairo::Context::new(&surface).map_err(error)?);
+            finish(surface.finish_output_stream())
+        }
+        ExportFormat::Pdf => {
+            let surface = cairo::PdfSurface::for_stream(page_width, page_height, Vec::<u8>::new())
+                .map_err(error)?;
+            draw_page(&cairo::Context::new(&surface).map_err(error)?);
+            finish(surface.finish_output_stream())
+        }
+    }
+}
+
+/// The caption wrapped to the width of the export and centered
+fn caption_layout(cr: &cairo::Context, text: &str, width: f64) -> pango::Layout {
+    let layout = pangocairo::functions::create_layout(cr);
+    let mut font = pango::FontDescription::from_string("Sans");
+    font.set_absolute_size(CAPTION_SIZE * pango::SCALE as f64);
+    layout.set_font_description(Some(&font));
+    layout.set_width(((width - CAPTION_PADDING * 2.0).max(1.0) * pango::SCALE as f64) as i32);
+    layout.set_wrap(pango::WrapMode::WordChar);
+    layout.set_alignment(pango::Alignment::Center);
+    layout.set_text(text);
+    layout
+}
+
+/// The document written to the stream of a finished vector surface
+fn finish(
+    stream: Result<Box<dyn Any>, cairo::StreamWithError>,
+) -> Result<Vec<u8>, ScreenshotError> {
+    let stream = stream.map_err(|e| ScreenshotError::PixbufError(e.error.to_string()))?;
+    stream
+        .downcast::<Vec<u8>>()
+        .map(|data| *data)
+        .map_err(|_| ScreenshotError::PixbufError("Unexpected output stream".to_string()))
+}
@@ -0,0 +1,72 @@
+//! Magic-wand selection by color similarity
+
+use crate::mask::Mask;
+use crate::pixels::Pixels;
+
+/// Default maximum per-channel color difference from the clicked pixel
+pub const DEFAULT_TOLERANCE: u8 = 32;
+
+/// How much the tolerance changes per key press
+pub const TOLERANCE_STEP: u8 = 8;
+
+/// Flood-fill from the pixel at (x, y) over neighbouring pixels whose color
+/// differs from it by at most `tolerance` on every channel. Returns the
+/// filled area, or None if the point is outside the image.
+pub fn flood_fill(pixels: &Pixels, x: i32, y: i32, tolerance: u8) -> Option<Mask> {
+    let (width, height) = (pixels.width, pixels.height);
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return None;
+    }
+
+    let seed = pixels.rgb(x, y);
+    let similar = |px: i32, py: i32| {
+        let rgb = pixels.rgb(px, py);
+        (0..3).all(|c| rgb[c].abs_diff(seed[c]) <= tolerance)
+    };
+
+    let index = |px: i32, py: i32| (py * width + px) as usize;
+    let mut filled = vec![false; (width * height) as usize];
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
+
+    // Scanline fill: fill a whole run of a row, then queue the rows above
+    // and below it
+    let mut stack = vec![(x, y)];
+    filled[index(x, y)] = true;
+    while let Some((sx, sy)) = stack.pop() {
+        let mut left = sx;
+        while left > 0 && !filled[index(left - 1, sy)] && similar(left - 1, sy) {
+            left -= 1;
+            filled[index(left, sy)] = true;
+        }
+        let mut right = sx;
+        while right < width - 1 && !filled[index(right + 1, sy)] && similar(right + 1, sy) {
+            right += 1;
+            filled[index(right, sy)] = true;
+        }
+
+        min_x = min_x.min(left);
+        max_x = max_x.max(right);
+        min_y = min_y.min(sy);
+        max_y = max_y.max(sy);
+
+        for ny in [sy - 1, sy + 1] {
+            if ny < 0 || ny >= height {
+                continue;
+            }
+            for nx in left..=right {
+                if !filled[index(nx, ny)] && similar(nx, ny) {
+                    filled[index(nx, ny)] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+    }
+
+    Some(Mask::from_fn(
+        min_x,
+        min_y,
+        max_x - min_x + 1,
+        max_y - min_y + 1,
+        |px, py| filled[index(px, py)],
+    ))
+}